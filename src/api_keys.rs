@@ -0,0 +1,450 @@
+use actix_web::http::header::{self, HeaderName};
+use actix_web::HttpRequest;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{fs, io};
+
+use crate::errors::ApiError;
+use crate::logging;
+use crate::rate_limit::{RateLimitDecision, RateLimiter};
+
+const X_API_KEY: HeaderName = HeaderName::from_static("x-api-key");
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeysFile {
+    #[serde(default)]
+    tiers: HashMap<String, TierConfig>,
+    #[serde(default)]
+    keys: Vec<KeyConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TierConfig {
+    max_requests: u32,
+    window_seconds: f64,
+    daily_quota: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyConfig {
+    key: String,
+    name: String,
+    tier: String,
+    #[serde(default = "enabled_by_default")]
+    enabled: bool,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+struct Tier {
+    limiter: RateLimiter<String>,
+    daily_quota: Option<u64>,
+}
+
+struct ApiKey {
+    name: String,
+    tier: String,
+    enabled: bool,
+}
+
+struct DailyUsage {
+    // Days since the Unix epoch, in UTC.
+    day: u64,
+    requests: u64,
+}
+
+/// API keys and the limits of the tiers they belong to. Requests with a key
+/// are limited by their tier rather than by address.
+pub struct ApiKeyStore {
+    keys: HashMap<String, ApiKey>,
+    tiers: HashMap<String, Tier>,
+    // Keyed by key name, so that a key can be rotated without resetting it.
+    usage: Mutex<HashMap<String, DailyUsage>>,
+    required: bool,
+}
+
+impl ApiKeyStore {
+    /// A store without keys, so every request is anonymous.
+    pub fn empty() -> Self {
+        Self {
+            keys: HashMap::new(),
+            tiers: HashMap::new(),
+            usage: Mutex::new(HashMap::new()),
+            required: false,
+        }
+    }
+
+    /// Loads the keys file; `required` turns away requests without a key.
+    pub fn from_file(path: &str, required: bool) -> io::Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| io::Error::new(err.kind(), format!("{path}: {err}")))?;
+        Self::from_toml(&text, required)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, format!("{path}: {err}")))
+    }
+
+    fn from_toml(text: &str, required: bool) -> Result<Self, String> {
+        let file: KeysFile = toml::from_str(text).map_err(|err| err.to_string())?;
+
+        // An empty bucket would never refill.
+        for (name, tier) in &file.tiers {
+            if tier.max_requests == 0 {
+                return Err(format!(
+                    "Tier '{name}' max_requests must be greater than 0, not 0."
+                ));
+            }
+            if tier.window_seconds.is_nan() || tier.window_seconds <= 0.0 {
+                return Err(format!(
+                    "Tier '{name}' window_seconds must be greater than 0, not {}.",
+                    tier.window_seconds
+                ));
+            }
+        }
+
+        let mut keys = HashMap::new();
+        for key in file.keys {
+            if !file.tiers.contains_key(&key.tier) {
+                return Err(format!(
+                    "Key '{}' has unknown tier '{}'.",
+                    key.name, key.tier
+                ));
+            }
+            let api_key = ApiKey {
+                name: key.name,
+                tier: key.tier,
+                enabled: key.enabled,
+            };
+            if let Some(duplicate) = keys.insert(key.key, api_key) {
+                return Err(format!("Key '{}' is listed twice.", duplicate.name));
+            }
+        }
+
+        let tiers = file
+            .tiers
+            .into_iter()
+            .map(|(name, tier)| {
+                let members = keys.values().filter(|key| key.tier == name).count();
+                let limiter = RateLimiter::new(
+                    tier.max_requests,
+                    tier.window_seconds,
+                    NonZeroUsize::new(members).unwrap_or(NonZeroUsize::MIN),
+                );
+                let tier = Tier {
+                    limiter,
+                    daily_quota: tier.daily_quota,
+                };
+                (name, tier)
+            })
+            .collect();
+
+        Ok(Self {
+            keys,
+            tiers,
+            usage: Mutex::new(HashMap::new()),
+            required,
+        })
+    }
+
+    /// Checks the key `req` was sent with against its tier's limits, returning
+    /// the key and the decision, or `None` for a request without a key.
+    /// Whatever the `cost`, the request counts once towards the daily quota.
+    pub fn check(
+        &self,
+        req: &HttpRequest,
+        cost: u32,
+    ) -> Result<Option<(String, RateLimitDecision)>, ApiError> {
+        let Some(key) = presented_key(req) else {
+            if self.required {
+                return Err(ApiError::unauthorized(
+                    "An API key is required.",
+                    logging::instance(req),
+                ));
+            }
+            return Ok(None);
+        };
+        let key = key.map_err(|detail| ApiError::unauthorized(detail, logging::instance(req)))?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let decision = self.check_key(key, cost, now, &logging::instance(req))?;
+        Ok(Some((key.to_string(), decision)))
+    }
+
+    /// Charges a key that `check` accepted for further work on the same
+    /// request, which has paid `paid` tokens so far, without counting it
+    /// towards the daily quota again.
+    pub fn charge(&self, key: &str, cost: u32, paid: u32) -> RateLimitDecision {
+        let api_key = &self.keys[key];
+        self.tiers[&api_key.tier]
+            .limiter
+            .check_more(api_key.name.clone(), cost, paid)
+    }
+
+    fn check_key(
+        &self,
+        key: &str,
+        cost: u32,
+        now: Duration,
+        instance: &str,
+    ) -> Result<RateLimitDecision, ApiError> {
+        let Some(api_key) = self.keys.get(key) else {
+            return Err(ApiError::unauthorized(
+                "The API key is not valid.",
+                instance,
+            ));
+        };
+        if !api_key.enabled {
+            return Err(ApiError::forbidden(
+                "The API key has been disabled.",
+                instance,
+            ));
+        }
+        let tier = &self.tiers[&api_key.tier];
+
+        let mut usage = self.usage.lock();
+        let today = now.as_secs() / SECONDS_PER_DAY;
+        let used = usage.entry(api_key.name.clone()).or_insert(DailyUsage {
+            day: today,
+            requests: 0,
+        });
+        if used.day != today {
+            *used = DailyUsage {
+                day: today,
+                requests: 0,
+            };
+        }
+        if let Some(quota) = tier.daily_quota.filter(|&quota| used.requests >= quota) {
+            let tomorrow = Duration::from_secs((today + 1) * SECONDS_PER_DAY);
+            return Err(ApiError::rate_limited(
+                format!("The daily quota of {quota} requests has been used up."),
+                instance,
+                tomorrow.saturating_sub(now),
+            ));
+        }
+
+        let decision = tier.limiter.check_n(api_key.name.clone(), cost);
+        if decision.allowed {
+            used.requests += 1;
+        }
+        Ok(decision)
+    }
+}
+
+// `Authorization: Bearer <key>` or `X-API-Key: <key>`; `None` when neither
+// header was sent.
+fn presented_key(req: &HttpRequest) -> Option<Result<&str, &'static str>> {
+    if let Some(value) = req.headers().get(header::AUTHORIZATION) {
+        return Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .map(str::trim)
+                .ok_or("Send the API key as a Bearer token."),
+        );
+    }
+    req.headers().get(X_API_KEY).map(|value| {
+        value
+            .to_str()
+            .map(str::trim)
+            .map_err(|_| "Invalid API key.")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use std::{env, process};
+
+    const KEYS: &str = r#"
+        [tiers.partner]
+        max_requests = 2
+        window_seconds = 60.0
+
+        [tiers.batch]
+        max_requests = 10
+        window_seconds = 1.0
+        daily_quota = 2
+
+        [[keys]]
+        key = "partner-key"
+        name = "acme"
+        tier = "partner"
+
+        [[keys]]
+        key = "batch-key"
+        name = "nightly"
+        tier = "batch"
+
+        [[keys]]
+        key = "old-key"
+        name = "retired"
+        tier = "partner"
+        enabled = false
+    "#;
+
+    #[test]
+    fn test_requests_without_a_key_are_anonymous_unless_required() {
+        // Given
+        let optional = ApiKeyStore::from_toml(KEYS, false).unwrap();
+        let required = ApiKeyStore::from_toml(KEYS, true).unwrap();
+        let req = TestRequest::default().to_http_request();
+
+        // When & Then
+        assert!(matches!(optional.check(&req, 1), Ok(None)));
+        assert!(matches!(
+            required.check(&req, 1),
+            Err(ApiError::Unauthorized { .. })
+        ));
+    }
+
+    #[test]
+    fn test_unknown_and_disabled_keys_are_rejected() {
+        // Given
+        let store = ApiKeyStore::from_toml(KEYS, false).unwrap();
+        let unknown = TestRequest::default()
+            .insert_header(("X-API-Key", "guessed"))
+            .to_http_request();
+        let disabled = TestRequest::default()
+            .insert_header(("Authorization", "Bearer old-key"))
+            .to_http_request();
+
+        // When & Then
+        assert!(matches!(
+            store.check(&unknown, 1),
+            Err(ApiError::Unauthorized { .. })
+        ));
+        assert!(matches!(
+            store.check(&disabled, 1),
+            Err(ApiError::Forbidden { .. })
+        ));
+    }
+
+    #[test]
+    fn test_keys_are_limited_by_their_tier() {
+        // Given
+        let store = ApiKeyStore::from_toml(KEYS, false).unwrap();
+        let now = Duration::from_secs(SECONDS_PER_DAY * 100);
+
+        // When
+        let allowed: Vec<bool> = (0..3)
+            .map(|_| store.check_key("partner-key", 1, now, "/").unwrap().allowed)
+            .collect();
+
+        // Then
+        assert_eq!(allowed, vec![true, true, false]);
+    }
+
+    #[test]
+    fn test_daily_quota_resets_at_midnight_utc() {
+        // Given
+        let store = ApiKeyStore::from_toml(KEYS, false).unwrap();
+        let noon = Duration::from_secs(SECONDS_PER_DAY * 100 + SECONDS_PER_DAY / 2);
+        let tomorrow = noon + Duration::from_secs(SECONDS_PER_DAY);
+
+        // When
+        store.check_key("batch-key", 1, noon, "/").unwrap();
+        store.check_key("batch-key", 1, noon, "/").unwrap();
+        let exhausted = store.check_key("batch-key", 1, noon, "/");
+
+        // Then
+        match exhausted {
+            Err(ApiError::RateLimited { retry_after, .. }) => {
+                assert_eq!(retry_after, Duration::from_secs(SECONDS_PER_DAY / 2));
+            }
+            other => panic!("Expected the daily quota to be used up, got {other:?}"),
+        }
+        assert!(store.check_key("batch-key", 1, tomorrow, "/").is_ok());
+    }
+
+    #[test]
+    fn test_keys_file_is_loaded_from_disk() {
+        // Given
+        let path = env::temp_dir().join(format!("wordle-solver-{}-keys.toml", process::id()));
+        fs::write(&path, KEYS).unwrap();
+        let path = path.to_str().unwrap();
+        let req = TestRequest::default()
+            .insert_header(("X-API-Key", "partner-key"))
+            .to_http_request();
+
+        // When
+        let store = ApiKeyStore::from_file(path, true).unwrap();
+
+        // Then
+        let (key, decision) = store.check(&req, 1).unwrap().unwrap();
+        assert_eq!(key, "partner-key");
+        assert!(decision.allowed);
+    }
+
+    #[test]
+    fn test_missing_keys_file_names_the_path() {
+        // Given
+        let path = env::temp_dir().join("wordle-solver-missing-keys.toml");
+        let path = path.to_str().unwrap();
+
+        // When
+        let err = ApiKeyStore::from_file(path, false).err().unwrap();
+
+        // Then
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().starts_with(&format!("{path}: ")), "{err}");
+    }
+
+    #[test]
+    fn test_keys_must_belong_to_a_known_tier() {
+        // Given
+        let text = r#"
+            [[keys]]
+            key = "k"
+            name = "lost"
+            tier = "gold"
+        "#;
+
+        // When
+        let result = ApiKeyStore::from_toml(text, false);
+
+        // Then
+        assert_eq!(
+            result.err(),
+            Some("Key 'lost' has unknown tier 'gold'.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_tiers_must_refill() {
+        // Given
+        let no_requests = r#"
+            [tiers.free]
+            max_requests = 0
+            window_seconds = 1.0
+        "#;
+        let no_window = r#"
+            [tiers.free]
+            max_requests = 1
+            window_seconds = -1.0
+        "#;
+
+        // When
+        let results =
+            [no_requests, no_window].map(|text| ApiKeyStore::from_toml(text, false).err());
+
+        // Then
+        assert_eq!(
+            results,
+            [
+                Some("Tier 'free' max_requests must be greater than 0, not 0.".to_string()),
+                Some("Tier 'free' window_seconds must be greater than 0, not -1.".to_string()),
+            ]
+        );
+    }
+}
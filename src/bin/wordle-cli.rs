@@ -0,0 +1,489 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::process::ExitCode;
+use wordle_solver::dictionary::{
+    get_all_words_from_file, ALLOWED_GUESSES_FILENAME, ANSWERS_FILENAME,
+};
+use wordle_solver::filters::select_words;
+use wordle_solver::models::Turn;
+use wordle_solver::{calculate_entropy_for_words, Color, Constraints, Guess, Word, WordIndex};
+
+const HELP: &str = "\
+Commands:
+  <pattern>          colours for the suggested word, e.g. gy..g
+  <word> <pattern>   colours for a word of your own, e.g. crane .y..g
+  undo               forget the last guess
+  list [n]           show the n best remaining words (default 20)
+  strategy [name]    show or switch strategy: entropy or answers
+  help               show this message
+  quit               exit
+
+Patterns use g for green, y for yellow and . (or b, x, -) for grey.";
+
+const USAGE: &str = "\
+Usage: wordle-cli [--answers <file>] [--guesses <file>] [--strategy <entropy|answers>]";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Strategy {
+    // Guess whichever remaining word splits the candidates best.
+    Entropy,
+    // Only guess words that could be the answer.
+    Answers,
+}
+
+impl Strategy {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "entropy" => Ok(Self::Entropy),
+            "answers" => Ok(Self::Answers),
+            other => Err(format!(
+                "Unknown strategy '{other}'; use entropy or answers."
+            )),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Entropy => "entropy",
+            Self::Answers => "answers",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Feedback {
+        word: Option<String>,
+        colors: [Color; 5],
+    },
+    Undo,
+    List(usize),
+    Strategy(Option<Strategy>),
+    Help,
+    Quit,
+}
+
+fn parse_command(line: &str) -> Result<Command, String> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    match parts.as_slice() {
+        ["undo"] => Ok(Command::Undo),
+        ["list"] => Ok(Command::List(20)),
+        ["list", n] => n
+            .parse()
+            .map(Command::List)
+            .map_err(|_| format!("'{n}' is not a number.")),
+        ["strategy"] => Ok(Command::Strategy(None)),
+        ["strategy", name] => Strategy::parse(name).map(|s| Command::Strategy(Some(s))),
+        ["help"] | ["?"] => Ok(Command::Help),
+        ["quit"] | ["exit"] => Ok(Command::Quit),
+        [pattern] => Ok(Command::Feedback {
+            word: None,
+            colors: parse_pattern(pattern)?,
+        }),
+        [word, pattern] => Ok(Command::Feedback {
+            word: Some(word.to_ascii_lowercase()),
+            colors: parse_pattern(pattern)?,
+        }),
+        _ => Err("Unrecognised command; type help for a list.".to_string()),
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Result<[Color; 5], String> {
+    let colors = pattern
+        .chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            'g' => Ok(Color::Green),
+            'y' => Ok(Color::Yellow),
+            '.' | '-' | 'b' | 'x' => Ok(Color::Grey),
+            other => Err(format!("'{other}' is not a colour; use g, y or .")),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    colors
+        .try_into()
+        .map_err(|_| "Patterns must have 5 colours.".to_string())
+}
+
+struct Solver<'a> {
+    words: &'a [Word],
+    index: WordIndex,
+    turns: Vec<Turn>,
+    guesses: Vec<Guess>,
+    strategy: Strategy,
+}
+
+impl<'a> Solver<'a> {
+    fn new(words: &'a [Word], strategy: Strategy) -> Self {
+        Self {
+            words,
+            index: WordIndex::new(words),
+            turns: Vec::new(),
+            guesses: Vec::new(),
+            strategy,
+        }
+    }
+
+    fn play(&mut self, turn: Turn) -> Result<(), String> {
+        let guesses = turn.to_guesses(self.turns.len())?.0;
+        self.guesses.extend(guesses);
+        self.turns.push(turn);
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Option<Turn> {
+        let turn = self.turns.pop()?;
+        let remaining = self.turns.len();
+        self.guesses.retain(|guess| guess.turn < remaining);
+        Some(turn)
+    }
+
+    fn ranked_candidates(&self) -> Vec<Word> {
+        let constraints = Constraints::from_guesses(&self.guesses);
+        let candidates = select_words(self.words, &self.index.filter(&constraints));
+        if candidates.is_empty() {
+            return Vec::new();
+        }
+        calculate_entropy_for_words(&candidates)
+    }
+
+    fn suggestion<'w>(&self, ranked: &'w [Word]) -> Option<&'w Word> {
+        match self.strategy {
+            Strategy::Entropy => ranked.first(),
+            Strategy::Answers => ranked.iter().find(|w| w.is_answer).or(ranked.first()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    answers_filename: String,
+    allowed_guesses_filename: String,
+    strategy: Strategy,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        answers_filename: ANSWERS_FILENAME.to_string(),
+        allowed_guesses_filename: ALLOWED_GUESSES_FILENAME.to_string(),
+        strategy: Strategy::Entropy,
+    };
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--answers", Some(path)) => options.answers_filename = path,
+            ("--guesses", Some(path)) => options.allowed_guesses_filename = path,
+            ("--strategy", Some(name)) => options.strategy = Strategy::parse(&name)?,
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    Ok(options)
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let words =
+        match get_all_words_from_file(&options.answers_filename, &options.allowed_guesses_filename)
+        {
+            Ok(words) => words,
+            Err(err) => {
+                eprintln!("Could not read the word lists: {err}");
+                return ExitCode::FAILURE;
+            }
+        };
+
+    let stdin = io::stdin();
+    let interactive = stdin.is_terminal();
+    match run(
+        &words,
+        options.strategy,
+        stdin.lock(),
+        io::stdout().lock(),
+        interactive,
+    ) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Prompts are only written when a person is typing at `input`.
+fn run(
+    words: &[Word],
+    strategy: Strategy,
+    input: impl BufRead,
+    mut stdout: impl Write,
+    interactive: bool,
+) -> io::Result<()> {
+    let mut solver = Solver::new(words, strategy);
+    let mut lines = input.lines();
+
+    if interactive {
+        writeln!(stdout, "Type help for a list of commands.")?;
+    }
+
+    let mut ranked = solver.ranked_candidates();
+    let mut changed = true;
+    loop {
+        let suggestion = solver.suggestion(&ranked).map(|w| w.word.clone());
+        if changed {
+            match &suggestion {
+                Some(word) => writeln!(stdout, "Suggestion: {word} ({} left)", ranked.len())?,
+                None => writeln!(stdout, "No words match; undo a guess to continue.")?,
+            }
+            changed = false;
+        }
+
+        if interactive {
+            write!(stdout, "> ")?;
+            stdout.flush()?;
+        }
+        let Some(line) = lines.next().transpose()? else {
+            return Ok(());
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match parse_command(&line) {
+            Ok(Command::Feedback { word, colors }) => {
+                let Some(word) = word.or(suggestion) else {
+                    writeln!(stdout, "There is no suggestion; give the word you guessed.")?;
+                    continue;
+                };
+                let solved = colors.iter().all(|&c| c == Color::Green);
+                if let Err(err) = solver.play(Turn { word, colors }) {
+                    writeln!(stdout, "{err}")?;
+                    continue;
+                }
+                if solved {
+                    writeln!(stdout, "Solved in {} guesses.", solver.turns.len())?;
+                    return Ok(());
+                }
+                ranked = solver.ranked_candidates();
+                changed = true;
+            }
+            Ok(Command::Undo) => {
+                match solver.undo() {
+                    Some(turn) => writeln!(stdout, "Forgot {}.", turn.word)?,
+                    None => writeln!(stdout, "Nothing to undo.")?,
+                }
+                ranked = solver.ranked_candidates();
+                changed = true;
+            }
+            Ok(Command::List(n)) => {
+                for word in ranked.iter().take(n) {
+                    let marker = if word.is_answer { "" } else { " (guess only)" };
+                    writeln!(stdout, "  {} {:.3}{marker}", word.word, word.entropy)?;
+                }
+            }
+            Ok(Command::Strategy(None)) => {
+                writeln!(stdout, "Strategy: {}", solver.strategy.name())?;
+            }
+            Ok(Command::Strategy(Some(strategy))) => {
+                solver.strategy = strategy;
+                writeln!(stdout, "Strategy: {}", strategy.name())?;
+                changed = true;
+            }
+            Ok(Command::Help) => writeln!(stdout, "{HELP}")?,
+            Ok(Command::Quit) => return Ok(()),
+            Err(err) => writeln!(stdout, "{err}")?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command_reads_feedback_for_suggested_and_own_words() {
+        // Given
+        let suggested = "gy..G";
+        let own = "CRANE bxy-g";
+
+        // When
+        let suggested_command = parse_command(suggested);
+        let own_command = parse_command(own);
+
+        // Then
+        use Color::{Green, Grey, Yellow};
+        assert_eq!(
+            suggested_command,
+            Ok(Command::Feedback {
+                word: None,
+                colors: [Green, Yellow, Grey, Grey, Green],
+            })
+        );
+        assert_eq!(
+            own_command,
+            Ok(Command::Feedback {
+                word: Some("crane".to_string()),
+                colors: [Grey, Grey, Yellow, Grey, Green],
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_pattern_rejects_bad_patterns() {
+        // Given
+        let wrong_length = "gyg";
+        let wrong_colour = "gyzgg";
+
+        // When & Then
+        assert_eq!(
+            parse_pattern(wrong_length),
+            Err("Patterns must have 5 colours.".to_string())
+        );
+        assert_eq!(
+            parse_pattern(wrong_colour),
+            Err("'z' is not a colour; use g, y or .".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_args_reads_paths_and_strategy() {
+        // Given
+        let args = [
+            "--answers",
+            "a.txt",
+            "--strategy",
+            "answers",
+            "--guesses",
+            "g.txt",
+        ];
+        let unknown_strategy = ["--strategy", "random"];
+        let missing_value = ["--answers"];
+
+        // When
+        let parse = |args: &[&str]| parse_args(args.iter().map(|arg| arg.to_string()));
+
+        // Then
+        assert_eq!(
+            parse(&args),
+            Ok(Options {
+                answers_filename: "a.txt".to_string(),
+                allowed_guesses_filename: "g.txt".to_string(),
+                strategy: Strategy::Answers,
+            })
+        );
+        assert_eq!(parse(&[]).unwrap().answers_filename, ANSWERS_FILENAME);
+        assert_eq!(
+            parse(&unknown_strategy),
+            Err("Unknown strategy 'random'; use entropy or answers.".to_string())
+        );
+        assert_eq!(parse(&missing_value), Err(USAGE.to_string()));
+    }
+
+    #[test]
+    fn test_run_plays_until_solved() {
+        // Given
+        let words = vec![
+            Word::new("crane".to_string(), true),
+            Word::new("slate".to_string(), true),
+            Word::new("trace".to_string(), false),
+        ];
+        let input = "\
+help
+strategy
+strategy answers
+list 5
+
+undo
+zzzzz
+no such command
+abcd ggggg
+crane .....
+.....
+undo
+slate ggggg
+";
+        let mut output = Vec::new();
+
+        // When
+        run(
+            &words,
+            Strategy::Entropy,
+            input.as_bytes(),
+            &mut output,
+            true,
+        )
+        .unwrap();
+
+        // Then
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("Type help for a list of commands.\nSuggestion: "));
+        assert!(output.contains("> "));
+        assert!(output.contains("Strategy: entropy\n"));
+        assert!(output.contains("Strategy: answers\n"));
+        assert!(output.contains("  trace 1.585 (guess only)\n"));
+        assert!(output.contains("Unrecognised command; type help for a list.\n"));
+        assert!(output.contains("Guessed words must have 5 letters.\n"));
+        assert!(output.contains("There is no suggestion; give the word you guessed.\n"));
+        assert!(output.contains("Nothing to undo.\n"));
+        assert!(output.contains("'z' is not a colour; use g, y or .\n"));
+        assert!(output.contains("No words match; undo a guess to continue.\n"));
+        assert!(output.contains("Forgot crane.\n"));
+        assert!(output.ends_with("Solved in 1 guesses.\n"));
+    }
+
+    #[test]
+    fn test_run_stops_at_the_end_of_input_or_on_quit() {
+        // Given
+        let words = vec![Word::new("crane".to_string(), true)];
+        let mut at_end = Vec::new();
+        let mut quit = Vec::new();
+
+        // When
+        run(&words, Strategy::Entropy, "".as_bytes(), &mut at_end, false).unwrap();
+        run(
+            &words,
+            Strategy::Entropy,
+            "quit\nhelp\n".as_bytes(),
+            &mut quit,
+            false,
+        )
+        .unwrap();
+
+        // Then
+        assert_eq!(
+            String::from_utf8(at_end).unwrap(),
+            "Suggestion: crane (1 left)\n"
+        );
+        assert_eq!(
+            String::from_utf8(quit).unwrap(),
+            "Suggestion: crane (1 left)\n"
+        );
+    }
+
+    #[test]
+    fn test_solver_undo_restores_candidates() {
+        // Given
+        let words: Vec<Word> = ["crane", "slate", "trace"]
+            .iter()
+            .map(|w| Word::new(w.to_string(), true))
+            .collect();
+        let mut solver = Solver::new(&words, Strategy::Entropy);
+        let before = solver.ranked_candidates().len();
+
+        // When
+        solver
+            .play(Turn {
+                word: "slate".to_string(),
+                colors: parse_pattern(".....").unwrap(),
+            })
+            .unwrap();
+        let narrowed = solver.ranked_candidates().len();
+        solver.undo();
+
+        // Then
+        assert_eq!(narrowed, 0);
+        assert_eq!(solver.ranked_candidates().len(), before);
+    }
+}
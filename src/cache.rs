@@ -0,0 +1,92 @@
+use lru::LruCache;
+use parking_lot::Mutex;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use wordle_solver::constraints::Constraints;
+use wordle_solver::models::PossibleWords;
+
+pub struct ResponseCache {
+    entries: Mutex<LruCache<Constraints, Arc<PossibleWords>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResponseCache {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, constraints: &Constraints) -> Option<Arc<PossibleWords>> {
+        let cached = self.entries.lock().get(constraints).cloned();
+        let counter = if cached.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        cached
+    }
+
+    /// Whether `constraints` are cached, without counting as a use.
+    pub fn contains(&self, constraints: &Constraints) -> bool {
+        self.entries.lock().contains(constraints)
+    }
+
+    pub fn insert(&self, constraints: Constraints, possible_words: Arc<PossibleWords>) {
+        self.entries.lock().put(constraints, possible_words);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wordle_solver::models::{Color, Guess};
+
+    fn construct_constraints(letter: char) -> Constraints {
+        Constraints::from_guesses(&[Guess {
+            turn: 0,
+            letter,
+            position: 0,
+            color: Color::Green,
+        }])
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used_and_counts_lookups() {
+        // Given
+        let cache = ResponseCache::new(NonZeroUsize::new(2).unwrap());
+        let possible_words = Arc::new(PossibleWords::new(Vec::new(), 0));
+        cache.insert(construct_constraints('a'), Arc::clone(&possible_words));
+        cache.insert(construct_constraints('b'), Arc::clone(&possible_words));
+
+        // When
+        let first = cache.get(&construct_constraints('a'));
+        cache.insert(construct_constraints('c'), possible_words);
+        let evicted = cache.get(&construct_constraints('b'));
+
+        // Then
+        assert!(first.is_some());
+        assert!(evicted.is_none(), "b was the least recently used entry");
+        assert!(cache.get(&construct_constraints('c')).is_some());
+        assert_eq!(cache.hits(), 2);
+        assert_eq!(cache.misses(), 1);
+    }
+}
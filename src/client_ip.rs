@@ -0,0 +1,270 @@
+use actix_web::http::header::{HeaderMap, HeaderName, FORWARDED};
+use actix_web::HttpRequest;
+use ipnet::{IpNet, Ipv6Net};
+use serde::Deserialize;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+const X_REAL_IP: HeaderName = HeaderName::from_static("x-real-ip");
+
+/// The header our proxies add the client's address to. Only that one is
+/// read: a client can send any of the others itself.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ForwardedHeader {
+    Forwarded,
+    #[default]
+    XForwardedFor,
+    XRealIp,
+}
+
+impl FromStr for ForwardedHeader {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "forwarded" => Ok(Self::Forwarded),
+            "x-forwarded-for" => Ok(Self::XForwardedFor),
+            "x-real-ip" => Ok(Self::XRealIp),
+            _ => Err(format!(
+                "expected forwarded, x-forwarded-for or x-real-ip, not {name}"
+            )),
+        }
+    }
+}
+
+/// Works out which client a request came from, believing forwarding headers
+/// only when they were added by one of our own proxies.
+pub struct ClientIpResolver {
+    trusted_proxies: Vec<IpNet>,
+    header: ForwardedHeader,
+    ipv6_prefix_length: Option<u8>,
+}
+
+impl ClientIpResolver {
+    pub fn new(
+        trusted_proxies: Vec<IpNet>,
+        header: ForwardedHeader,
+        ipv6_prefix_length: Option<u8>,
+    ) -> Self {
+        Self {
+            trusted_proxies,
+            header,
+            ipv6_prefix_length: ipv6_prefix_length.map(|length| length.min(128)),
+        }
+    }
+
+    /// The address `req` came from.
+    pub fn client(&self, req: &HttpRequest) -> IpAddr {
+        let peer = req
+            .peer_addr()
+            .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |addr| addr.ip());
+        self.client_ip(peer, req.headers())
+    }
+
+    /// The address to rate limit `client` by. IPv6 clients are grouped by
+    /// their prefix when a prefix length is configured, since one user can
+    /// easily rotate through a whole /64.
+    pub fn rate_limit_key(&self, client: IpAddr) -> IpAddr {
+        match (client, self.ipv6_prefix_length) {
+            (IpAddr::V6(ip), Some(length)) => Ipv6Net::new(ip, length)
+                .map_or(client, |network| IpAddr::V6(network.trunc().addr())),
+            _ => client,
+        }
+    }
+
+    fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let peer = peer.to_canonical();
+        if !self.is_trusted(peer) {
+            return peer;
+        }
+
+        // Each proxy appends the address it received the request from, so walk
+        // back from our side until we reach one we don't run.
+        let mut client = peer;
+        for hop in forwarding_chain(headers, self.header).into_iter().rev() {
+            let Some(hop) = hop.map(|ip| ip.to_canonical()) else {
+                break;
+            };
+            client = hop;
+            if !self.is_trusted(hop) {
+                break;
+            }
+        }
+        client
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies
+            .iter()
+            .any(|network| network.contains(&ip))
+    }
+}
+
+// The addresses a request was forwarded for, oldest first, from `header`.
+// Entries that are not addresses, such as `unknown`, are `None`.
+fn forwarding_chain(headers: &HeaderMap, header: ForwardedHeader) -> Vec<Option<IpAddr>> {
+    let values = |name| {
+        headers
+            .get_all(name)
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+    };
+
+    match header {
+        ForwardedHeader::Forwarded => values(FORWARDED)
+            .filter_map(|element| {
+                element.split(';').find_map(|pair| {
+                    let (key, value) = pair.trim().split_once('=')?;
+                    key.eq_ignore_ascii_case("for")
+                        .then(|| parse_node(value.trim_matches('"')))
+                })
+            })
+            .collect(),
+        ForwardedHeader::XForwardedFor => values(X_FORWARDED_FOR).map(parse_node).collect(),
+        ForwardedHeader::XRealIp => values(X_REAL_IP).map(parse_node).collect(),
+    }
+}
+
+// Accepts `192.0.2.1`, `192.0.2.1:4711`, `2001:db8::1` and `[2001:db8::1]:4711`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    node.parse::<IpAddr>()
+        .or_else(|_| node.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .ok()
+        .or_else(|| {
+            let ip = node.strip_prefix('[')?.split(']').next()?;
+            ip.parse().ok()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn resolver(header: ForwardedHeader) -> ClientIpResolver {
+        ClientIpResolver::new(vec!["10.0.0.0/8".parse().unwrap()], header, None)
+    }
+
+    fn peer(ip: &str) -> SocketAddr {
+        SocketAddr::new(ip.parse().unwrap(), 40000)
+    }
+
+    #[test]
+    fn test_forwarding_headers_are_ignored_from_untrusted_peers() {
+        // Given
+        let req = TestRequest::default()
+            .peer_addr(peer("203.0.113.9"))
+            .insert_header(("X-Forwarded-For", "198.51.100.1"))
+            .to_http_request();
+
+        // When
+        let client = resolver(ForwardedHeader::XForwardedFor).client(&req);
+
+        // Then
+        assert_eq!(client, "203.0.113.9".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_client_is_the_last_untrusted_hop() {
+        // Given
+        let forwarded_for = TestRequest::default()
+            .peer_addr(peer("10.0.0.2"))
+            .insert_header(("X-Forwarded-For", "198.51.100.1, 203.0.113.7, 10.0.0.1"))
+            .to_http_request();
+        let forwarded = TestRequest::default()
+            .peer_addr(peer("10.0.0.2"))
+            .insert_header((
+                "Forwarded",
+                r#"for=198.51.100.1;proto=https, for="[2001:db8::17]:4711""#,
+            ))
+            .insert_header(("X-Real-IP", "192.0.2.1"))
+            .to_http_request();
+        let real_ip = TestRequest::default()
+            .peer_addr(peer("10.0.0.2"))
+            .insert_header(("X-Real-IP", "192.0.2.1"))
+            .to_http_request();
+
+        // When & Then
+        assert_eq!(
+            resolver(ForwardedHeader::XForwardedFor).client(&forwarded_for),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            resolver(ForwardedHeader::Forwarded).client(&forwarded),
+            "2001:db8::17".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            resolver(ForwardedHeader::XRealIp).client(&real_ip),
+            "192.0.2.1".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_headers_the_proxy_does_not_write_are_ignored() {
+        // Given
+        let req = TestRequest::default()
+            .peer_addr(peer("10.0.0.2"))
+            .insert_header(("Forwarded", "for=192.0.2.66"))
+            .insert_header(("X-Real-IP", "192.0.2.77"))
+            .insert_header(("X-Forwarded-For", "203.0.113.7"))
+            .to_http_request();
+        let without_header = TestRequest::default()
+            .peer_addr(peer("10.0.0.2"))
+            .insert_header(("Forwarded", "for=192.0.2.66"))
+            .insert_header(("X-Real-IP", "192.0.2.77"))
+            .to_http_request();
+
+        // When
+        let client = resolver(ForwardedHeader::XForwardedFor).client(&req);
+        let fallback = resolver(ForwardedHeader::XForwardedFor).client(&without_header);
+
+        // Then
+        assert_eq!(client, "203.0.113.7".parse::<IpAddr>().unwrap());
+        assert_eq!(fallback, "10.0.0.2".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_forwarded_header_names_parse_in_any_case() {
+        assert_eq!("X-Real-IP".parse(), Ok(ForwardedHeader::XRealIp));
+        assert_eq!("forwarded".parse(), Ok(ForwardedHeader::Forwarded));
+        assert!("via".parse::<ForwardedHeader>().is_err());
+    }
+
+    #[test]
+    fn test_unparseable_hops_stop_at_the_last_known_address() {
+        // Given
+        let req = TestRequest::default()
+            .peer_addr(peer("10.0.0.2"))
+            .insert_header(("X-Forwarded-For", "198.51.100.1, unknown, 10.0.0.1"))
+            .to_http_request();
+
+        // When
+        let client = resolver(ForwardedHeader::XForwardedFor).client(&req);
+
+        // Then
+        assert_eq!(client, "10.0.0.1".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_ipv6_clients_share_a_bucket_per_prefix() {
+        // Given
+        let resolver = ClientIpResolver::new(Vec::new(), ForwardedHeader::default(), Some(64));
+        let first = TestRequest::default()
+            .peer_addr(peer("2001:db8:1:2:aaaa::1"))
+            .to_http_request();
+        let second = TestRequest::default()
+            .peer_addr(peer("2001:db8:1:2:bbbb::2"))
+            .to_http_request();
+
+        // When
+        let first_key = resolver.rate_limit_key(resolver.client(&first));
+        let second_key = resolver.rate_limit_key(resolver.client(&second));
+
+        // Then
+        assert_eq!(first_key, second_key);
+        assert_eq!(first_key, "2001:db8:1:2::".parse::<IpAddr>().unwrap());
+    }
+}
@@ -0,0 +1,163 @@
+use futures_channel::oneshot;
+use log::error;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::future::Future;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Threads of their own for scoring, so that a few expensive requests can't
+/// hold up the async workers serving everything else.
+pub struct ComputePool {
+    pool: ThreadPool,
+    // Jobs waiting for a thread or running on one.
+    jobs: Arc<AtomicUsize>,
+    max_jobs: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ComputeError {
+    /// Every thread is busy and `queue_size` jobs are already waiting.
+    Saturated,
+    /// The job panicked.
+    Failed,
+}
+
+impl ComputePool {
+    /// A pool of `threads` threads, one per core by default, that turns work
+    /// away once `queue_size` jobs are waiting for one.
+    pub fn new(threads: Option<usize>, queue_size: usize) -> io::Result<Self> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads.unwrap_or(0))
+            .thread_name(|index| format!("compute-{index}"))
+            .panic_handler(|_| error!("A scoring job panicked"))
+            .build()
+            .map_err(io::Error::other)?;
+        Ok(Self {
+            max_jobs: pool.current_num_threads() + queue_size,
+            pool,
+            jobs: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// Starts `job` on the pool straight away, and resolves once it is done
+    /// without blocking the caller's thread. Parallel iterators inside `job`
+    /// stay on the pool too.
+    pub fn run<T: Send + 'static>(
+        &self,
+        job: impl FnOnce() -> T + Send + 'static,
+    ) -> impl Future<Output = Result<T, ComputeError>> {
+        let started = self.spawn(job);
+        async move { started?.await.map_err(|_| ComputeError::Failed) }
+    }
+
+    fn spawn<T: Send + 'static>(
+        &self,
+        job: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<oneshot::Receiver<T>, ComputeError> {
+        if self.jobs.fetch_add(1, Ordering::SeqCst) >= self.max_jobs {
+            self.jobs.fetch_sub(1, Ordering::SeqCst);
+            return Err(ComputeError::Saturated);
+        }
+        let done = Done(Arc::clone(&self.jobs));
+        let (sender, receiver) = oneshot::channel();
+        self.pool.spawn(move || {
+            // The place is freed before the caller hears back, panic or not.
+            let result = panic::catch_unwind(AssertUnwindSafe(job));
+            drop(done);
+            match result {
+                Ok(result) => {
+                    let _ = sender.send(result);
+                }
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        });
+        Ok(receiver)
+    }
+
+    /// Runs `job` on the pool from a thread outside it, blocking until it is
+    /// done. Unlike `run`, it is never turned away.
+    pub fn install<T: Send>(&self, job: impl FnOnce() -> T + Send) -> T {
+        self.pool.install(job)
+    }
+
+    /// Jobs running or waiting for a thread.
+    pub fn jobs(&self) -> usize {
+        self.jobs.load(Ordering::SeqCst)
+    }
+
+    /// Whether `run` would turn a job away right now.
+    pub fn is_saturated(&self) -> bool {
+        self.jobs() >= self.max_jobs
+    }
+}
+
+/// A flag for a job to check, set once this is dropped, as it is when the
+/// request waiting for the job goes away.
+pub struct CancelOnDrop(Arc<AtomicBool>);
+
+impl CancelOnDrop {
+    pub fn new() -> Self {
+        Self(Arc::new(AtomicBool::new(false)))
+    }
+
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.0)
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+// Frees a job's place in the queue however it ends, panics included.
+struct Done(Arc<AtomicUsize>);
+
+impl Drop for Done {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[actix_web::test]
+    async fn test_jobs_beyond_the_queue_are_turned_away() {
+        // Given
+        let pool = ComputePool::new(Some(1), 1).unwrap();
+        let (release, blocked) = mpsc::channel::<()>();
+        let running = pool.run(move || blocked.recv().unwrap());
+        let waiting = pool.run(|| 2);
+
+        // When
+        let turned_away = pool.run(|| 3).await;
+        release.send(()).unwrap();
+
+        // Then
+        assert_eq!(turned_away, Err(ComputeError::Saturated));
+        assert_eq!(running.await, Ok(()));
+        assert_eq!(waiting.await, Ok(2));
+        assert_eq!(pool.jobs(), 0);
+        assert_eq!(pool.run(|| 4).await, Ok(4));
+    }
+
+    #[actix_web::test]
+    async fn test_panicking_jobs_fail_and_free_their_place() {
+        // Given
+        let pool = ComputePool::new(Some(1), 0).unwrap();
+
+        // When
+        let result = pool.run(|| -> u32 { panic!("scoring went wrong") }).await;
+
+        // Then
+        assert_eq!(result, Err(ComputeError::Failed));
+        assert_eq!(pool.jobs(), 0);
+        assert_eq!(pool.run(|| 4).await, Ok(4));
+    }
+}
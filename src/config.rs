@@ -0,0 +1,706 @@
+use ipnet::IpNet;
+use serde::Deserialize;
+use std::fmt::Display;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+use std::{env, fs, io};
+
+use crate::client_ip::ForwardedHeader;
+use crate::state::ScoringBudget;
+use wordle_solver::dictionary::{ALLOWED_GUESSES_FILENAME, ANSWERS_FILENAME};
+
+const CONFIG_PATH_VAR: &str = "WORDLE_SOLVER_CONFIG";
+const DEFAULT_CONFIG_PATH: &str = "wordle_solver.toml";
+
+/// Settings read from `wordle_solver.toml` (or the file named by
+/// `WORDLE_SOLVER_CONFIG`), then overridden by `WORDLE_SOLVER_*` variables.
+#[derive(Deserialize, Default, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub words: WordsConfig,
+    pub rate_limit: RateLimitConfig,
+    pub api_keys: ApiKeysConfig,
+    pub cache: CacheConfig,
+    pub sessions: SessionsConfig,
+    pub compute: ComputeConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind_address: String,
+    pub port: u16,
+    /// Defaults to one worker per core.
+    pub workers: Option<NonZeroUsize>,
+    pub cors_origins: Vec<String>,
+    /// Proxies whose forwarding header is believed when working out a
+    /// client's address.
+    pub trusted_proxies: Vec<IpNet>,
+    /// The one header those proxies write the client's address to.
+    pub forwarded_header: ForwardedHeader,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind_address: "0.0.0.0".to_string(),
+            port: 5307,
+            workers: None,
+            cors_origins: vec!["https://wordlesolver.umbra.mom".to_string()],
+            trusted_proxies: Vec::new(),
+            forwarded_header: ForwardedHeader::default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WordsConfig {
+    pub answers: String,
+    pub allowed_guesses: String,
+}
+
+impl Default for WordsConfig {
+    fn default() -> Self {
+        WordsConfig {
+            answers: ANSWERS_FILENAME.to_string(),
+            allowed_guesses: ALLOWED_GUESSES_FILENAME.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub max_requests: u32,
+    pub window_seconds: f64,
+    /// Clients tracked at once; the least recently seen is forgotten beyond this.
+    pub max_tracked_clients: NonZeroUsize,
+    /// Rate limit IPv6 clients by this prefix, e.g. 64, instead of by address.
+    pub ipv6_prefix_length: Option<u8>,
+    /// Clients that are never rate limited, such as load balancers.
+    pub allow: Vec<IpNet>,
+    /// Clients that are always turned away.
+    pub deny: Vec<IpNet>,
+    /// `POST /possible-words` and `GET /live`.
+    pub suggestions: RouteLimitConfig,
+    /// `/sessions` and everything below it.
+    pub sessions: RouteLimitConfig,
+    /// `GET /metrics`.
+    pub admin: RouteLimitConfig,
+    /// Messages on each `GET /live` connection. Their scoring is charged to
+    /// `suggestions` as well.
+    pub live: RouteLimitConfig,
+}
+
+/// A route's own limit; unset values fall back to those of `[rate_limit]`.
+#[derive(Deserialize, Default, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RouteLimitConfig {
+    pub max_requests: Option<u32>,
+    pub window_seconds: Option<f64>,
+}
+
+impl RateLimitConfig {
+    /// The request limit and window for a route.
+    pub fn limit_for(&self, route: &RouteLimitConfig) -> (u32, f64) {
+        (
+            route.max_requests.unwrap_or(self.max_requests),
+            route.window_seconds.unwrap_or(self.window_seconds),
+        )
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            max_requests: 1,
+            window_seconds: 1.0,
+            max_tracked_clients: NonZeroUsize::new(100_000).unwrap(),
+            ipv6_prefix_length: None,
+            allow: Vec::new(),
+            deny: Vec::new(),
+            suggestions: RouteLimitConfig::default(),
+            sessions: RouteLimitConfig::default(),
+            admin: RouteLimitConfig::default(),
+            live: RouteLimitConfig {
+                max_requests: Some(10),
+                window_seconds: Some(1.0),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Default, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiKeysConfig {
+    /// A TOML file of API keys and their tiers. Without one, every client is
+    /// limited by address.
+    pub file: Option<String>,
+    /// Turn away requests that don't send a key.
+    pub required: bool,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    pub capacity: NonZeroUsize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            capacity: NonZeroUsize::new(256).unwrap(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionsConfig {
+    pub ttl_seconds: u64,
+    /// Sessions kept at once; the least recently used is forgotten beyond this.
+    pub max_sessions: NonZeroUsize,
+}
+
+impl SessionsConfig {
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_seconds)
+    }
+}
+
+impl Default for SessionsConfig {
+    fn default() -> Self {
+        SessionsConfig {
+            ttl_seconds: 30 * 60,
+            max_sessions: NonZeroUsize::new(100_000).unwrap(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ComputeConfig {
+    /// Threads ranking candidates. Defaults to one per core.
+    pub threads: Option<usize>,
+    /// Rankings that may wait for a thread before requests get a `503`.
+    pub queue_size: usize,
+    /// Guess and candidate pairs a ranking may compare before it estimates
+    /// entropies from a sample.
+    pub max_comparisons: usize,
+    /// How long a ranking may take before it estimates the entropies of the
+    /// guesses left.
+    pub deadline_ms: u64,
+}
+
+impl ComputeConfig {
+    pub fn budget(&self) -> ScoringBudget {
+        ScoringBudget {
+            max_comparisons: self.max_comparisons,
+            deadline: Duration::from_millis(self.deadline_ms),
+        }
+    }
+}
+
+impl Default for ComputeConfig {
+    fn default() -> Self {
+        ComputeConfig {
+            threads: None,
+            queue_size: 64,
+            max_comparisons: 25_000_000,
+            deadline_ms: 2000,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// An `env_logger` filter. `RUST_LOG` takes precedence when it is set.
+    pub level: String,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: "actix_web=info,wordle_solver=info".to_string(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> io::Result<Self> {
+        Self::load_from(DEFAULT_CONFIG_PATH, |name| env::var(name).ok())
+    }
+
+    fn load_from(default_path: &str, var: impl Fn(&str) -> Option<String>) -> io::Result<Self> {
+        let mut config = match var(CONFIG_PATH_VAR) {
+            Some(path) => Self::from_file(&path)?,
+            None if Path::new(default_path).exists() => Self::from_file(default_path)?,
+            None => Config::default(),
+        };
+        config.apply_overrides(var)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &str) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        toml::from_str(&text).map_err(|err| invalid_config(format!("{path}: {err}")))
+    }
+
+    fn apply_overrides(&mut self, var: impl Fn(&str) -> Option<String>) -> io::Result<()> {
+        let server = &mut self.server;
+        override_with(&var, "WORDLE_SOLVER_BIND_ADDRESS", &mut server.bind_address)?;
+        override_with(&var, "WORDLE_SOLVER_PORT", &mut server.port)?;
+        if let Some(workers) = parse_var(&var, "WORDLE_SOLVER_WORKERS")? {
+            server.workers = Some(workers);
+        }
+        override_list(&var, "WORDLE_SOLVER_CORS_ORIGINS", &mut server.cors_origins)?;
+        override_list(
+            &var,
+            "WORDLE_SOLVER_TRUSTED_PROXIES",
+            &mut server.trusted_proxies,
+        )?;
+        override_with(
+            &var,
+            "WORDLE_SOLVER_FORWARDED_HEADER",
+            &mut server.forwarded_header,
+        )?;
+
+        override_with(&var, "WORDLE_SOLVER_ANSWERS_FILE", &mut self.words.answers)?;
+        override_with(
+            &var,
+            "WORDLE_SOLVER_ALLOWED_GUESSES_FILE",
+            &mut self.words.allowed_guesses,
+        )?;
+
+        let rate_limit = &mut self.rate_limit;
+        override_with(
+            &var,
+            "WORDLE_SOLVER_RATE_LIMIT_MAX_REQUESTS",
+            &mut rate_limit.max_requests,
+        )?;
+        override_with(
+            &var,
+            "WORDLE_SOLVER_RATE_LIMIT_WINDOW_SECONDS",
+            &mut rate_limit.window_seconds,
+        )?;
+        override_with(
+            &var,
+            "WORDLE_SOLVER_RATE_LIMIT_MAX_TRACKED_CLIENTS",
+            &mut rate_limit.max_tracked_clients,
+        )?;
+        if let Some(length) = parse_var(&var, "WORDLE_SOLVER_RATE_LIMIT_IPV6_PREFIX_LENGTH")? {
+            rate_limit.ipv6_prefix_length = Some(length);
+        }
+        override_list(
+            &var,
+            "WORDLE_SOLVER_RATE_LIMIT_ALLOW",
+            &mut rate_limit.allow,
+        )?;
+        override_list(&var, "WORDLE_SOLVER_RATE_LIMIT_DENY", &mut rate_limit.deny)?;
+        for (prefix, route) in [
+            (
+                "WORDLE_SOLVER_RATE_LIMIT_SUGGESTIONS",
+                &mut rate_limit.suggestions,
+            ),
+            (
+                "WORDLE_SOLVER_RATE_LIMIT_SESSIONS",
+                &mut rate_limit.sessions,
+            ),
+            ("WORDLE_SOLVER_RATE_LIMIT_ADMIN", &mut rate_limit.admin),
+            ("WORDLE_SOLVER_RATE_LIMIT_LIVE", &mut rate_limit.live),
+        ] {
+            if let Some(max_requests) = parse_var(&var, &format!("{prefix}_MAX_REQUESTS"))? {
+                route.max_requests = Some(max_requests);
+            }
+            if let Some(window) = parse_var(&var, &format!("{prefix}_WINDOW_SECONDS"))? {
+                route.window_seconds = Some(window);
+            }
+        }
+
+        if let Some(file) = var("WORDLE_SOLVER_API_KEYS_FILE") {
+            self.api_keys.file = Some(file);
+        }
+        override_with(
+            &var,
+            "WORDLE_SOLVER_API_KEYS_REQUIRED",
+            &mut self.api_keys.required,
+        )?;
+
+        override_with(
+            &var,
+            "WORDLE_SOLVER_CACHE_CAPACITY",
+            &mut self.cache.capacity,
+        )?;
+        override_with(
+            &var,
+            "WORDLE_SOLVER_SESSION_TTL_SECONDS",
+            &mut self.sessions.ttl_seconds,
+        )?;
+        override_with(
+            &var,
+            "WORDLE_SOLVER_SESSION_MAX_SESSIONS",
+            &mut self.sessions.max_sessions,
+        )?;
+
+        if let Some(threads) = parse_var(&var, "WORDLE_SOLVER_COMPUTE_THREADS")? {
+            self.compute.threads = Some(threads);
+        }
+        override_with(
+            &var,
+            "WORDLE_SOLVER_COMPUTE_QUEUE_SIZE",
+            &mut self.compute.queue_size,
+        )?;
+        override_with(
+            &var,
+            "WORDLE_SOLVER_COMPUTE_MAX_COMPARISONS",
+            &mut self.compute.max_comparisons,
+        )?;
+        override_with(
+            &var,
+            "WORDLE_SOLVER_COMPUTE_DEADLINE_MS",
+            &mut self.compute.deadline_ms,
+        )?;
+        Ok(())
+    }
+
+    // Values that parse but would make no sense, such as an empty bucket that
+    // never refills.
+    fn validate(&self) -> io::Result<()> {
+        let rate_limit = &self.rate_limit;
+        require_positive(
+            "rate_limit.max_requests",
+            "WORDLE_SOLVER_RATE_LIMIT_MAX_REQUESTS",
+            rate_limit.max_requests,
+        )?;
+        require_positive(
+            "rate_limit.window_seconds",
+            "WORDLE_SOLVER_RATE_LIMIT_WINDOW_SECONDS",
+            rate_limit.window_seconds,
+        )?;
+        for (name, route) in [
+            ("suggestions", &rate_limit.suggestions),
+            ("sessions", &rate_limit.sessions),
+            ("admin", &rate_limit.admin),
+            ("live", &rate_limit.live),
+        ] {
+            let var = format!("WORDLE_SOLVER_RATE_LIMIT_{}", name.to_uppercase());
+            if let Some(max_requests) = route.max_requests {
+                require_positive(
+                    &format!("rate_limit.{name}.max_requests"),
+                    &format!("{var}_MAX_REQUESTS"),
+                    max_requests,
+                )?;
+            }
+            if let Some(window) = route.window_seconds {
+                require_positive(
+                    &format!("rate_limit.{name}.window_seconds"),
+                    &format!("{var}_WINDOW_SECONDS"),
+                    window,
+                )?;
+            }
+        }
+        require_positive(
+            "compute.max_comparisons",
+            "WORDLE_SOLVER_COMPUTE_MAX_COMPARISONS",
+            self.compute.max_comparisons,
+        )
+    }
+}
+
+fn override_with<T>(
+    var: &impl Fn(&str) -> Option<String>,
+    name: &str,
+    target: &mut T,
+) -> io::Result<()>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Some(value) = parse_var(var, name)? {
+        *target = value;
+    }
+    Ok(())
+}
+
+// Comma separated, e.g. `WORDLE_SOLVER_TRUSTED_PROXIES=10.0.0.0/8,fd00::/8`.
+fn override_list<T>(
+    var: &impl Fn(&str) -> Option<String>,
+    name: &str,
+    target: &mut Vec<T>,
+) -> io::Result<()>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Some(values) = var(name) {
+        *target = values
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|err| invalid_config(format!("{name}={values}: {err}")))
+            })
+            .collect::<io::Result<_>>()?;
+    }
+    Ok(())
+}
+
+fn parse_var<T>(var: &impl Fn(&str) -> Option<String>, name: &str) -> io::Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    var(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|err| invalid_config(format!("{name}={value}: {err}")))
+        })
+        .transpose()
+}
+
+fn require_positive<T>(setting: &str, var: &str, value: T) -> io::Result<()>
+where
+    T: PartialOrd + Default + Display,
+{
+    // Written so that NaN fails too.
+    if value > T::default() {
+        return Ok(());
+    }
+    Err(invalid_config(format!(
+        "{setting} ({var}) must be greater than 0, not {value}"
+    )))
+}
+
+fn invalid_config(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::process;
+
+    fn write_config(name: &str, text: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("wordle-solver-{}-{name}", process::id()));
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_config_is_loaded_from_the_named_file() {
+        // Given
+        let path = write_config("named.toml", "[server]\nport = 8080\n");
+        let path = path.to_str().unwrap().to_string();
+
+        // When
+        let config = Config::load_from("missing.toml", |name| match name {
+            CONFIG_PATH_VAR => Some(path.clone()),
+            "WORDLE_SOLVER_BIND_ADDRESS" => Some("127.0.0.1".to_string()),
+            _ => None,
+        })
+        .unwrap();
+
+        // Then
+        assert_eq!(config.server.port, 8080);
+        assert_eq!(config.server.bind_address, "127.0.0.1");
+    }
+
+    #[test]
+    fn test_shipped_config_file_matches_the_defaults() {
+        // When
+        let shipped = Config::load_from(DEFAULT_CONFIG_PATH, |_| None).unwrap();
+        let without_file = Config::load_from("missing.toml", |_| None).unwrap();
+
+        // Then
+        assert_eq!(shipped, Config::default());
+        assert_eq!(without_file, Config::default());
+    }
+
+    #[test]
+    fn test_unreadable_config_files_are_errors() {
+        // Given
+        let missing = env::temp_dir().join("wordle-solver-missing.toml");
+        let missing = missing.to_str().unwrap().to_string();
+        let invalid = write_config("invalid.toml", "[server]\nport = \"high\"\n");
+        let invalid = invalid.to_str().unwrap().to_string();
+
+        // When
+        let missing_result = Config::load_from(DEFAULT_CONFIG_PATH, |name| {
+            (name == CONFIG_PATH_VAR).then(|| missing.clone())
+        });
+        let invalid_result = Config::load_from(DEFAULT_CONFIG_PATH, |name| {
+            (name == CONFIG_PATH_VAR).then(|| invalid.clone())
+        });
+
+        // Then
+        assert_eq!(missing_result.unwrap_err().kind(), io::ErrorKind::NotFound);
+        let err = invalid_result.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(
+            err.to_string().starts_with(&format!("{invalid}: ")),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_config_file_keeps_defaults_for_missing_keys() {
+        // Given
+        let text = r#"
+            [server]
+            port = 8080
+            cors_origins = ["https://staging.example.com"]
+
+            [rate_limit]
+            max_requests = 5
+
+            [rate_limit.sessions]
+            window_seconds = 10.0
+        "#;
+
+        // When
+        let config: Config = toml::from_str(text).unwrap();
+
+        // Then
+        assert_eq!(config.server.port, 8080);
+        assert_eq!(config.server.bind_address, "0.0.0.0");
+        assert_eq!(
+            config.server.cors_origins,
+            vec!["https://staging.example.com"]
+        );
+        assert_eq!(config.rate_limit.max_requests, 5);
+        assert_eq!(config.rate_limit.window_seconds, 1.0);
+        assert_eq!(
+            config.rate_limit.limit_for(&config.rate_limit.sessions),
+            (5, 10.0)
+        );
+        assert_eq!(
+            config.rate_limit.limit_for(&config.rate_limit.suggestions),
+            (5, 1.0)
+        );
+        assert_eq!(config.words, WordsConfig::default());
+    }
+
+    #[test]
+    fn test_environment_overrides_the_file() {
+        // Given
+        let mut config = Config::default();
+        let vars = HashMap::from([
+            ("WORDLE_SOLVER_PORT", "9000"),
+            ("WORDLE_SOLVER_WORKERS", "2"),
+            ("WORDLE_SOLVER_TRUSTED_PROXIES", "10.0.0.0/8,fd00::/8"),
+            ("WORDLE_SOLVER_FORWARDED_HEADER", "Forwarded"),
+            ("WORDLE_SOLVER_RATE_LIMIT_SUGGESTIONS_MAX_REQUESTS", "3"),
+            ("WORDLE_SOLVER_COMPUTE_THREADS", "2"),
+            ("WORDLE_SOLVER_COMPUTE_DEADLINE_MS", "500"),
+            (
+                "WORDLE_SOLVER_CORS_ORIGINS",
+                "https://a.example, https://b.example",
+            ),
+        ]);
+
+        // When
+        config
+            .apply_overrides(|name| vars.get(name).map(|v| v.to_string()))
+            .unwrap();
+
+        // Then
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.server.workers, NonZeroUsize::new(2));
+        assert_eq!(
+            config.server.trusted_proxies,
+            vec![
+                "10.0.0.0/8".parse::<IpNet>().unwrap(),
+                "fd00::/8".parse().unwrap()
+            ]
+        );
+        assert_eq!(config.server.forwarded_header, ForwardedHeader::Forwarded);
+        assert_eq!(
+            config.server.cors_origins,
+            vec!["https://a.example", "https://b.example"]
+        );
+        assert_eq!(config.rate_limit.suggestions.max_requests, Some(3));
+        assert_eq!(config.compute.threads, Some(2));
+        assert_eq!(
+            config.compute.budget(),
+            ScoringBudget {
+                max_comparisons: 25_000_000,
+                deadline: Duration::from_millis(500),
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_override_names_the_variable() {
+        // Given
+        let mut config = Config::default();
+        let vars = ["WORDLE_SOLVER_CACHE_CAPACITY", "WORDLE_SOLVER_WORKERS"];
+
+        // When
+        let messages = vars.map(|var| {
+            config
+                .apply_overrides(|name| (name == var).then(|| "0".to_string()))
+                .unwrap_err()
+                .to_string()
+        });
+
+        // Then
+        for (message, var) in messages.iter().zip(vars) {
+            assert!(message.starts_with(&format!("{var}=0")), "{message}");
+        }
+    }
+
+    #[test]
+    fn test_pointless_limits_name_the_setting_and_variable() {
+        // Given
+        let mut negative_window = Config::default();
+        negative_window
+            .apply_overrides(|name| {
+                (name == "WORDLE_SOLVER_RATE_LIMIT_WINDOW_SECONDS").then(|| "-1".to_string())
+            })
+            .unwrap();
+        let zero_route_window: Config = toml::from_str(
+            r#"
+            [rate_limit.sessions]
+            window_seconds = 0.0
+        "#,
+        )
+        .unwrap();
+        let mut zero_requests = Config::default();
+        zero_requests.rate_limit.max_requests = 0;
+        let mut zero_comparisons = Config::default();
+        zero_comparisons.compute.max_comparisons = 0;
+
+        // When
+        let messages = [
+            negative_window,
+            zero_route_window,
+            zero_requests,
+            zero_comparisons,
+        ]
+        .map(|config| config.validate().unwrap_err().to_string());
+
+        // Then
+        assert_eq!(
+            messages,
+            [
+                "rate_limit.window_seconds (WORDLE_SOLVER_RATE_LIMIT_WINDOW_SECONDS) must be greater than 0, not -1",
+                "rate_limit.sessions.window_seconds (WORDLE_SOLVER_RATE_LIMIT_SESSIONS_WINDOW_SECONDS) must be greater than 0, not 0",
+                "rate_limit.max_requests (WORDLE_SOLVER_RATE_LIMIT_MAX_REQUESTS) must be greater than 0, not 0",
+                "compute.max_comparisons (WORDLE_SOLVER_COMPUTE_MAX_COMPARISONS) must be greater than 0, not 0",
+            ]
+        );
+        assert!(Config::default().validate().is_ok());
+    }
+}
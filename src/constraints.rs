@@ -0,0 +1,271 @@
+use std::collections::BTreeMap;
+
+use crate::models::{Color, Guess};
+
+pub const WORD_LENGTH: usize = 5;
+pub const ALPHABET_SIZE: usize = 26;
+
+/// Everything the guesses so far say about the answer, independent of the
+/// order and numbering of the turns that produced it.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Constraints {
+    greens: [Option<u8>; WORD_LENGTH],
+    // Bitmask of letters, indexed by `letter - b'a'`, ruled out at each position.
+    forbidden: [u32; WORD_LENGTH],
+    min_counts: [u8; ALPHABET_SIZE],
+    max_counts: [u8; ALPHABET_SIZE],
+    contradictory: bool,
+}
+
+impl Default for Constraints {
+    fn default() -> Self {
+        Constraints {
+            greens: [None; WORD_LENGTH],
+            forbidden: [0; WORD_LENGTH],
+            min_counts: [0; ALPHABET_SIZE],
+            max_counts: [WORD_LENGTH as u8; ALPHABET_SIZE],
+            contradictory: false,
+        }
+    }
+}
+
+impl Constraints {
+    /// Builds the constraints for `guesses`, which must only contain
+    /// lowercase ASCII letters at positions 0 to 4 (see `GuessBody`).
+    pub fn from_guesses(guesses: &[Guess]) -> Self {
+        let mut constraints = Constraints::default();
+
+        let mut turns: BTreeMap<usize, Vec<&Guess>> = BTreeMap::new();
+        for guess in guesses {
+            turns.entry(guess.turn).or_default().push(guess);
+        }
+
+        for turn in turns.values() {
+            let mut coloured = [0u8; ALPHABET_SIZE];
+            let mut greyed = [false; ALPHABET_SIZE];
+
+            for guess in turn {
+                let letter = guess.letter as u8;
+                let index = letter_index(letter);
+                match guess.color {
+                    Color::Green => {
+                        coloured[index] = coloured[index].saturating_add(1);
+                        constraints.set_green(guess.position, letter);
+                    }
+                    Color::Yellow => {
+                        coloured[index] = coloured[index].saturating_add(1);
+                        constraints.forbidden[guess.position] |= 1 << index;
+                    }
+                    Color::Grey => {
+                        greyed[index] = true;
+                        constraints.forbidden[guess.position] |= 1 << index;
+                    }
+                }
+            }
+
+            // Within a turn, the green and yellow tiles of a letter give a lower
+            // bound on its count, and a grey tile of the same letter makes it exact.
+            for index in 0..ALPHABET_SIZE {
+                constraints.min_counts[index] = constraints.min_counts[index].max(coloured[index]);
+                if greyed[index] {
+                    constraints.max_counts[index] =
+                        constraints.max_counts[index].min(coloured[index]);
+                }
+            }
+        }
+
+        constraints.normalise();
+        constraints
+    }
+
+    // The straightforward check that `WordIndex::filter` is tested against.
+    #[cfg(test)]
+    pub fn matches(&self, word: &[u8; WORD_LENGTH]) -> bool {
+        if self.contradictory {
+            return false;
+        }
+
+        let mut counts = [0u8; ALPHABET_SIZE];
+        for (position, &letter) in word.iter().enumerate() {
+            let index = letter_index(letter);
+            if self.greens[position].is_some_and(|green| green != letter)
+                || self.forbidden[position] & (1 << index) != 0
+            {
+                return false;
+            }
+            counts[index] += 1;
+        }
+
+        counts
+            .iter()
+            .zip(self.min_counts.iter().zip(&self.max_counts))
+            .all(|(count, (min, max))| min <= count && count <= max)
+    }
+
+    /// Returns false when no word can match, e.g. a letter that was marked
+    /// green in one turn and grey everywhere in another.
+    pub fn is_satisfiable(&self) -> bool {
+        !self.contradictory
+    }
+
+    pub fn green(&self, position: usize) -> Option<u8> {
+        self.greens[position]
+    }
+
+    pub fn forbidden_letters(&self, position: usize) -> impl Iterator<Item = u8> + '_ {
+        (b'a'..=b'z')
+            .filter(move |&letter| self.forbidden[position] & (1 << letter_index(letter)) != 0)
+    }
+
+    /// The minimum and maximum number of times `letter` can appear.
+    pub fn count_bounds(&self, letter: u8) -> (u8, u8) {
+        let index = letter_index(letter);
+        (self.min_counts[index], self.max_counts[index])
+    }
+
+    fn set_green(&mut self, position: usize, letter: u8) {
+        match self.greens[position] {
+            Some(green) if green != letter => self.contradictory = true,
+            _ => self.greens[position] = Some(letter),
+        }
+    }
+
+    // Drops information implied by the rest, so that equivalent guess
+    // histories compare and hash equal.
+    fn normalise(&mut self) {
+        let mut green_counts = [0u8; ALPHABET_SIZE];
+        for (position, green) in self.greens.iter().enumerate() {
+            if let Some(letter) = *green {
+                let index = letter_index(letter);
+                if self.forbidden[position] & (1 << index) != 0 {
+                    self.contradictory = true;
+                }
+                green_counts[index] += 1;
+                self.forbidden[position] = 0;
+            }
+        }
+
+        for (min, green_count) in self.min_counts.iter_mut().zip(green_counts) {
+            *min = (*min).max(green_count);
+        }
+
+        let min_total: usize = self.min_counts.iter().map(|&c| usize::from(c)).sum();
+        if min_total > WORD_LENGTH
+            || self
+                .min_counts
+                .iter()
+                .zip(&self.max_counts)
+                .any(|(min, max)| min > max)
+        {
+            self.contradictory = true;
+        }
+
+        if self.contradictory {
+            *self = Constraints {
+                contradictory: true,
+                ..Constraints::default()
+            };
+        }
+    }
+}
+
+fn letter_index(letter: u8) -> usize {
+    usize::from(letter - b'a')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn construct_turn(turn: usize, word: &str, colors: [Color; 5]) -> Vec<Guess> {
+        word.chars()
+            .zip(colors)
+            .enumerate()
+            .map(|(position, (letter, color))| Guess {
+                turn,
+                letter,
+                position,
+                color,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn from_guesses_ignores_turn_order_and_numbering() {
+        // Given
+        use Color::{Green, Grey, Yellow};
+        let crane = construct_turn(0, "crane", [Grey, Yellow, Grey, Grey, Green]);
+        let route = construct_turn(1, "route", [Green, Grey, Grey, Grey, Green]);
+        let in_order: Vec<Guess> = crane.iter().chain(&route).cloned().collect();
+        let renumbered: Vec<Guess> = construct_turn(7, "route", [Green, Grey, Grey, Grey, Green])
+            .into_iter()
+            .chain(construct_turn(
+                3,
+                "crane",
+                [Grey, Yellow, Grey, Grey, Green],
+            ))
+            .rev()
+            .collect();
+
+        // When
+        let expected = Constraints::from_guesses(&in_order);
+        let actual = Constraints::from_guesses(&renumbered);
+
+        // Then
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn matches_applies_counts_from_grey_duplicates() {
+        // Given
+        use Color::{Green, Grey, Yellow};
+        let guesses = construct_turn(0, "geese", [Grey, Yellow, Grey, Grey, Green]);
+
+        // When
+        let constraints = Constraints::from_guesses(&guesses);
+
+        // Then
+        assert!(constraints.matches(b"eyrie"));
+        assert!(
+            !constraints.matches(b"theme"),
+            "an e was grey in the middle"
+        );
+        assert!(!constraints.matches(b"emcee"), "only two e's are allowed");
+    }
+
+    #[test]
+    fn is_satisfiable_detects_contradictions_across_turns() {
+        // Given
+        use Color::{Green, Grey};
+        let guesses: Vec<Guess> = construct_turn(0, "crane", [Green, Grey, Grey, Grey, Grey])
+            .into_iter()
+            .chain(construct_turn(1, "cocoa", [Grey, Grey, Grey, Grey, Grey]))
+            .collect();
+
+        // When
+        let constraints = Constraints::from_guesses(&guesses);
+
+        // Then
+        assert!(!constraints.is_satisfiable());
+        assert!(!constraints.matches(b"cloud"));
+    }
+
+    #[test]
+    fn from_guesses_survives_hundreds_of_tiles_in_a_turn() {
+        // Given
+        let guesses: Vec<Guess> = (0..300)
+            .map(|position| Guess {
+                turn: 0,
+                letter: 'e',
+                position: position % WORD_LENGTH,
+                color: Color::Yellow,
+            })
+            .collect();
+
+        // When
+        let constraints = Constraints::from_guesses(&guesses);
+
+        // Then
+        assert!(!constraints.is_satisfiable());
+    }
+}
@@ -0,0 +1,179 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+};
+
+use crate::models::Word;
+
+pub const ALLOWED_GUESSES_FILENAME: &str = "wordle-nyt-allowed-guesses.txt";
+pub const ANSWERS_FILENAME: &str = "wordle-nyt-answers.txt";
+
+pub fn get_all_words_from_file(
+    answers_filename: &str,
+    allowed_guesses_filename: &str,
+) -> io::Result<Vec<Word>> {
+    fn read_words_from_file(filename: &str, is_answer: bool) -> io::Result<Vec<Word>> {
+        let file = File::open(filename)?;
+        let r = BufReader::new(file);
+        let mut words = Vec::new();
+        for (index, line) in r.lines().enumerate() {
+            let word = parse_line(&line?, is_answer).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{filename}, line {}: {err}", index + 1),
+                )
+            })?;
+            words.extend(word);
+        }
+        Ok(words)
+    }
+
+    let mut words = read_words_from_file(answers_filename, true)?;
+    let allowed_guesses = read_words_from_file(allowed_guesses_filename, false)?;
+
+    words.extend(allowed_guesses);
+    Ok(words)
+}
+
+/// Builds the dictionary from word lists already in memory, one word per line.
+pub fn words_from_lists(answers: &str, allowed_guesses: &str) -> Result<Vec<Word>, String> {
+    let mut words = parse_list("answers", answers, true)?;
+    words.extend(parse_list("allowed guesses", allowed_guesses, false)?);
+    Ok(words)
+}
+
+fn parse_list(name: &str, list: &str, is_answer: bool) -> Result<Vec<Word>, String> {
+    list.lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            parse_line(line, is_answer)
+                .map_err(|err| format!("{name}, line {}: {err}", index + 1))
+                .transpose()
+        })
+        .collect()
+}
+
+// Blank lines are skipped, and words may be padded or in upper case.
+fn parse_line(line: &str, is_answer: bool) -> Result<Option<Word>, String> {
+    let word = line.trim();
+    if word.is_empty() {
+        return Ok(None);
+    }
+    Word::try_new(word, is_answer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::{env, fs, process};
+
+    fn write_list(name: &str, words: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("wordle-solver-{}-{name}", process::id()));
+        fs::write(&path, words).unwrap();
+        path
+    }
+
+    #[test]
+    fn get_all_words_from_file_puts_answers_first() {
+        // Given
+        let answers = write_list("answers.txt", "crane\nslate\n");
+        let allowed_guesses = write_list("allowed.txt", "soare\n");
+
+        // When
+        let words =
+            get_all_words_from_file(answers.to_str().unwrap(), allowed_guesses.to_str().unwrap())
+                .unwrap();
+
+        // Then
+        let words: Vec<(&str, bool)> = words
+            .iter()
+            .map(|word| (word.word.as_str(), word.is_answer))
+            .collect();
+        assert_eq!(
+            words,
+            vec![("crane", true), ("slate", true), ("soare", false)]
+        );
+        assert_eq!(
+            words_from_lists("crane\nslate", "soare").unwrap().len(),
+            words.len()
+        );
+    }
+
+    #[test]
+    fn get_all_words_from_file_tidies_words_and_skips_blank_lines() {
+        // Given
+        let answers = write_list("tidy-answers.txt", " CRANE\n\n  \nSlate\r\n");
+        let allowed_guesses = write_list("tidy-allowed.txt", "");
+
+        // When
+        let words =
+            get_all_words_from_file(answers.to_str().unwrap(), allowed_guesses.to_str().unwrap())
+                .unwrap();
+
+        // Then
+        let words: Vec<&str> = words.iter().map(|word| word.word.as_str()).collect();
+        assert_eq!(words, vec!["crane", "slate"]);
+    }
+
+    #[test]
+    fn get_all_words_from_file_names_the_line_that_is_not_a_word() {
+        // Given
+        let answers = write_list("bad-answers.txt", "crane\n");
+        let allowed_guesses = write_list("bad-allowed.txt", "soare\ncran\n");
+        let accented = write_list("accented.txt", "crané\n");
+        let (answers, allowed_guesses, accented) = (
+            answers.to_str().unwrap(),
+            allowed_guesses.to_str().unwrap(),
+            accented.to_str().unwrap(),
+        );
+
+        // When
+        let short = get_all_words_from_file(answers, allowed_guesses).unwrap_err();
+        let not_ascii = get_all_words_from_file(accented, allowed_guesses).unwrap_err();
+
+        // Then
+        assert_eq!(short.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            short.to_string(),
+            format!("{allowed_guesses}, line 2: 'cran' is not a word of 5 letters.")
+        );
+        assert_eq!(not_ascii.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            not_ascii.to_string(),
+            format!("{accented}, line 1: 'crané' is not a word of 5 letters.")
+        );
+    }
+
+    #[test]
+    fn words_from_lists_names_the_list_and_line_that_is_not_a_word() {
+        // Given
+        let answers = "crane\n\nSLATE";
+        let allowed_guesses = "soare\nab";
+
+        // When
+        let words = words_from_lists(answers, "").unwrap();
+        let result = words_from_lists(answers, allowed_guesses);
+
+        // Then
+        assert_eq!(words.len(), 2);
+        assert_eq!(words[1].word, "slate");
+        assert_eq!(
+            result.err(),
+            Some("allowed guesses, line 2: 'ab' is not a word of 5 letters.".to_string())
+        );
+    }
+
+    #[test]
+    fn get_all_words_from_file_fails_for_a_missing_list() {
+        // Given
+        let answers = write_list("present.txt", "crane\n");
+        let missing = env::temp_dir().join("wordle-solver-missing.txt");
+
+        // When
+        let result = get_all_words_from_file(answers.to_str().unwrap(), missing.to_str().unwrap());
+
+        // Then
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
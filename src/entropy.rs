@@ -1,15 +1,105 @@
 use crate::models::Word;
 use itertools::Itertools;
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
+use std::cmp;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
 
-pub fn calculate_entropy_for_words(words: &[Word]) -> Vec<Word> {
+// The fewest candidates an estimate is made from, however small the budget,
+// and all that guesses scored past the deadline get.
+const MIN_SAMPLE_SIZE: usize = 64;
+
+/// How much work `calculate_entropy_within` may do before it settles for
+/// estimates.
+pub struct Budget<'a> {
+    /// Most guess and candidate pairs to compare. Beyond it, every guess is
+    /// scored against an evenly spread sample of the candidates.
+    pub max_comparisons: usize,
+    /// Guesses not yet scored when it passes are scored against a small
+    /// sample instead. Those estimates are too rough to rank against the
+    /// rest, so they follow them in the order they were given.
+    pub deadline: Option<Instant>,
+    /// Checked before scoring each guess; once set, scoring gives up.
+    pub cancelled: &'a AtomicBool,
+}
+
+pub struct Ranking {
+    pub words: Vec<Word>,
+    /// Some entropies were estimated from a sample of the candidates.
+    pub partial: bool,
+}
+
+pub fn calculate_entropy_for_words(words: &[&Word]) -> Vec<Word> {
     let word_bytes: Vec<[u8; 5]> = words.iter().map(|w| w.bytes).collect();
+    rank(words, |i| {
+        Some((
+            calculate_entropy_for_word(word_bytes[i], &word_bytes),
+            false,
+        ))
+    })
+    .unwrap_or_default()
+}
+
+/// `calculate_entropy_for_words` within `budget`, or `None` if it was
+/// cancelled.
+pub fn calculate_entropy_within(words: &[&Word], budget: &Budget) -> Option<Ranking> {
+    let word_bytes: Vec<[u8; 5]> = words.iter().map(|w| w.bytes).collect();
+    let sample_size = (budget.max_comparisons / words.len().max(1)).max(MIN_SAMPLE_SIZE);
+    let candidates = sample(&word_bytes, sample_size);
+    let late_candidates = sample(&word_bytes, MIN_SAMPLE_SIZE);
+    let late = AtomicBool::new(false);
+
+    let words = rank(words, |i| {
+        if budget.cancelled.load(Ordering::Relaxed) {
+            return None;
+        }
+        let overdue = budget
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline);
+        if overdue {
+            late.store(true, Ordering::Relaxed);
+            return Some((
+                calculate_entropy_for_word(word_bytes[i], &late_candidates),
+                true,
+            ));
+        }
+        Some((
+            calculate_entropy_for_word(word_bytes[i], &candidates),
+            false,
+        ))
+    })?;
+    Some(Ranking {
+        words,
+        partial: candidates.len() < word_bytes.len()
+            || (late.into_inner() && late_candidates.len() < word_bytes.len()),
+    })
+}
+
+// `size` candidates spread evenly through them, or all of them if there are
+// few enough.
+fn sample(candidates: &[[u8; 5]], size: usize) -> Vec<[u8; 5]> {
+    let size = size.min(candidates.len());
+    (0..size)
+        .map(|k| candidates[k * candidates.len() / size])
+        .collect()
+}
 
+// Sorted by descending entropy, except that words whose raw entropy is flagged
+// as late come last in their original order.
+fn rank(
+    words: &[&Word],
+    raw_entropy: impl Fn(usize) -> Option<(f32, bool)> + Sync,
+) -> Option<Vec<Word>> {
     let answer_count = words.iter().filter(|w| w.is_answer).count().max(1) as f32;
 
-    words
-        .par_iter()
+    #[cfg(feature = "parallel")]
+    let words_iter = words.par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let words_iter = words.iter();
+
+    let scored = words_iter
         .enumerate()
         .map(|(i, word)| {
             let extra_letters: u8 = word
@@ -31,21 +121,36 @@ pub fn calculate_entropy_for_words(words: &[Word]) -> Vec<Word> {
                 0.0
             };
 
-            let raw_entropy = calculate_entropy_for_word(word_bytes[i], &word_bytes);
-            Word {
+            let (raw_entropy, late) = raw_entropy(i)?;
+            let word = Word {
                 word: word.word.clone(),
                 entropy: raw_entropy * penalty_multiplier + answer_bonus,
                 is_answer: word.is_answer,
                 bytes: word.bytes,
-            }
+            };
+            Some((word, late))
         })
-        .collect::<Vec<Word>>()
-        .into_iter()
-        .sorted_by(|a, b| b.entropy.total_cmp(&a.entropy))
-        .collect()
+        .collect::<Option<Vec<(Word, bool)>>>()?;
+    Some(
+        scored
+            .into_iter()
+            .sorted_by(|(a, a_late), (b, b_late)| {
+                a_late.cmp(b_late).then_with(|| {
+                    if *a_late {
+                        cmp::Ordering::Equal
+                    } else {
+                        b.entropy.total_cmp(&a.entropy)
+                    }
+                })
+            })
+            .map(|(word, _)| word)
+            .collect(),
+    )
 }
 
-fn compute_pattern(guess: [u8; 5], candidate: [u8; 5]) -> u8 {
+/// The colours Wordle shows for `guess` against `candidate`, encoded in base 3
+/// with position 0 as the least significant digit (0 grey, 1 yellow, 2 green).
+pub fn compute_pattern(guess: [u8; 5], candidate: [u8; 5]) -> u8 {
     let mut counts = [0u8; 26];
     for b in candidate {
         counts[(b - b'a') as usize] += 1;
@@ -97,3 +202,120 @@ fn calculate_entropy_for_word(guess: [u8; 5], candidates: &[[u8; 5]]) -> f32 {
         })
         .sum()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `count` distinct words, which share some letters and not others.
+    fn generated_words(count: usize, is_answer: bool) -> Vec<Word> {
+        (0..count)
+            .map(|i| {
+                let letter = |n: usize| char::from(b'a' + (n % 26) as u8);
+                let word: String = [letter(i), letter(i / 26), 'r', letter(i + 7), 'e']
+                    .iter()
+                    .collect();
+                Word::new(word, is_answer)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compute_pattern_only_colours_repeated_letters_once() {
+        // Given
+        let guess = *b"geese";
+        let answer = *b"eyrie";
+
+        // When
+        let pattern = compute_pattern(guess, answer);
+
+        // Then
+        // Grey, yellow, grey, grey, green.
+        assert_eq!(pattern, 3 + 2 * 81);
+    }
+
+    #[test]
+    fn test_over_budget_rankings_are_estimated_from_a_sample() {
+        // Given
+        let words = generated_words(100, true);
+        let words: Vec<&Word> = words.iter().collect();
+        let cancelled = AtomicBool::new(false);
+        let budget = |max_comparisons| Budget {
+            max_comparisons,
+            deadline: None,
+            cancelled: &cancelled,
+        };
+
+        // When
+        let full = calculate_entropy_within(&words, &budget(100 * 100)).unwrap();
+        let estimated = calculate_entropy_within(&words, &budget(100 * 70)).unwrap();
+        cancelled.store(true, Ordering::Relaxed);
+        let abandoned = calculate_entropy_within(&words, &budget(100 * 100));
+
+        // Then
+        assert!(!full.partial);
+        let exact = calculate_entropy_for_words(&words);
+        assert_eq!(
+            full.words.iter().map(|w| &w.word).collect::<Vec<_>>(),
+            exact.iter().map(|w| &w.word).collect::<Vec<_>>()
+        );
+        assert!(estimated.partial);
+        assert_eq!(estimated.words.len(), words.len());
+        assert!(abandoned.is_none());
+    }
+
+    #[test]
+    fn test_tiny_budgets_still_sample_enough_candidates() {
+        // Given
+        let words = generated_words(200, false);
+        let words: Vec<&Word> = words.iter().collect();
+        let cancelled = AtomicBool::new(false);
+        let budget = Budget {
+            max_comparisons: 1,
+            deadline: None,
+            cancelled: &cancelled,
+        };
+
+        // When
+        let estimated = calculate_entropy_within(&words, &budget).unwrap();
+
+        // Then
+        // Scored against a single candidate, every guess would get nothing.
+        assert!(estimated.partial);
+        assert!(
+            estimated.words[0].entropy > 1.0,
+            "{}",
+            estimated.words[0].entropy
+        );
+        assert_eq!(sample(&[*b"crane"; 200], 1).len(), 1);
+        assert_eq!(
+            sample(&[*b"crane"; 200], MIN_SAMPLE_SIZE).len(),
+            MIN_SAMPLE_SIZE
+        );
+    }
+
+    #[test]
+    fn test_guesses_scored_late_keep_their_order() {
+        // Given
+        let words = generated_words(200, false);
+        let words: Vec<&Word> = words.iter().collect();
+        let cancelled = AtomicBool::new(false);
+        let budget = Budget {
+            max_comparisons: usize::MAX,
+            deadline: Some(Instant::now()),
+            cancelled: &cancelled,
+        };
+
+        // When
+        let late = calculate_entropy_within(&words, &budget).unwrap();
+
+        // Then
+        assert!(late.partial);
+        assert_eq!(
+            late.words.iter().map(|w| &w.word).collect::<Vec<_>>(),
+            words.iter().map(|w| &w.word).collect::<Vec<_>>()
+        );
+        let exact = calculate_entropy_for_words(&words);
+        assert_ne!(exact[0].word, words[0].word);
+    }
+}
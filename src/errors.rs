@@ -1,6 +1,13 @@
-use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use actix_web::{
+    http::{header, StatusCode},
+    HttpResponse, ResponseError,
+};
 use serde::Serialize;
 use std::fmt;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+use crate::rate_limit::whole_seconds;
 
 #[derive(Debug)]
 pub enum ApiError {
@@ -8,15 +15,42 @@ pub enum ApiError {
         detail: String,
         instance: Option<String>,
     },
+    Unauthorized {
+        detail: String,
+        instance: Option<String>,
+    },
+    Forbidden {
+        detail: String,
+        instance: Option<String>,
+    },
+    NotFound {
+        detail: String,
+        instance: Option<String>,
+    },
+    Conflict {
+        detail: String,
+        instance: Option<String>,
+    },
     RateLimited {
         detail: String,
         instance: Option<String>,
+        retry_after: Duration,
+    },
+    Internal {
+        detail: String,
+        instance: Option<String>,
+    },
+    Unavailable {
+        detail: String,
+        instance: Option<String>,
     },
 }
 
-#[derive(Serialize)]
+/// An RFC 9457 problem details document.
+#[derive(Serialize, ToSchema)]
 pub struct ProblemDetails {
     #[serde(rename = "type")]
+    #[schema(example = "urn:wordle-solver:problem:bad-request")]
     pub problem_type: &'static str,
     pub title: &'static str,
     pub status: u16,
@@ -24,6 +58,9 @@ pub struct ProblemDetails {
     pub detail: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub instance: Option<String>,
+    /// Seconds to wait before retrying a rate limited request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
 }
 
 impl ApiError {
@@ -34,10 +71,57 @@ impl ApiError {
         }
     }
 
-    pub fn rate_limited(detail: impl Into<String>, instance: impl Into<String>) -> Self {
+    pub fn unauthorized(detail: impl Into<String>, instance: impl Into<String>) -> Self {
+        Self::Unauthorized {
+            detail: detail.into(),
+            instance: Some(instance.into()),
+        }
+    }
+
+    pub fn forbidden(detail: impl Into<String>, instance: impl Into<String>) -> Self {
+        Self::Forbidden {
+            detail: detail.into(),
+            instance: Some(instance.into()),
+        }
+    }
+
+    pub fn not_found(detail: impl Into<String>, instance: impl Into<String>) -> Self {
+        Self::NotFound {
+            detail: detail.into(),
+            instance: Some(instance.into()),
+        }
+    }
+
+    pub fn conflict(detail: impl Into<String>, instance: impl Into<String>) -> Self {
+        Self::Conflict {
+            detail: detail.into(),
+            instance: Some(instance.into()),
+        }
+    }
+
+    pub fn rate_limited(
+        detail: impl Into<String>,
+        instance: impl Into<String>,
+        retry_after: Duration,
+    ) -> Self {
         Self::RateLimited {
             detail: detail.into(),
             instance: Some(instance.into()),
+            retry_after,
+        }
+    }
+
+    pub fn internal(detail: impl Into<String>, instance: impl Into<String>) -> Self {
+        Self::Internal {
+            detail: detail.into(),
+            instance: Some(instance.into()),
+        }
+    }
+
+    pub fn unavailable(detail: impl Into<String>, instance: impl Into<String>) -> Self {
+        Self::Unavailable {
+            detail: detail.into(),
+            instance: Some(instance.into()),
         }
     }
 
@@ -49,13 +133,67 @@ impl ApiError {
                 status: StatusCode::BAD_REQUEST.as_u16(),
                 detail: Some(detail.clone()),
                 instance: instance.clone(),
+                retry_after: None,
+            },
+            Self::Unauthorized { detail, instance } => ProblemDetails {
+                problem_type: "urn:wordle-solver:problem:unauthorized",
+                title: "Unauthorized",
+                status: StatusCode::UNAUTHORIZED.as_u16(),
+                detail: Some(detail.clone()),
+                instance: instance.clone(),
+                retry_after: None,
             },
-            Self::RateLimited { detail, instance } => ProblemDetails {
+            Self::Forbidden { detail, instance } => ProblemDetails {
+                problem_type: "urn:wordle-solver:problem:forbidden",
+                title: "Forbidden",
+                status: StatusCode::FORBIDDEN.as_u16(),
+                detail: Some(detail.clone()),
+                instance: instance.clone(),
+                retry_after: None,
+            },
+            Self::NotFound { detail, instance } => ProblemDetails {
+                problem_type: "urn:wordle-solver:problem:not-found",
+                title: "Not Found",
+                status: StatusCode::NOT_FOUND.as_u16(),
+                detail: Some(detail.clone()),
+                instance: instance.clone(),
+                retry_after: None,
+            },
+            Self::Conflict { detail, instance } => ProblemDetails {
+                problem_type: "urn:wordle-solver:problem:conflict",
+                title: "Conflict",
+                status: StatusCode::CONFLICT.as_u16(),
+                detail: Some(detail.clone()),
+                instance: instance.clone(),
+                retry_after: None,
+            },
+            Self::RateLimited {
+                detail,
+                instance,
+                retry_after,
+            } => ProblemDetails {
                 problem_type: "urn:wordle-solver:problem:rate-limit-exceeded",
                 title: "Too Many Requests",
                 status: StatusCode::TOO_MANY_REQUESTS.as_u16(),
                 detail: Some(detail.clone()),
                 instance: instance.clone(),
+                retry_after: Some(whole_seconds(*retry_after)),
+            },
+            Self::Internal { detail, instance } => ProblemDetails {
+                problem_type: "urn:wordle-solver:problem:internal-error",
+                title: "Internal Server Error",
+                status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                detail: Some(detail.clone()),
+                instance: instance.clone(),
+                retry_after: None,
+            },
+            Self::Unavailable { detail, instance } => ProblemDetails {
+                problem_type: "urn:wordle-solver:problem:unavailable",
+                title: "Service Unavailable",
+                status: StatusCode::SERVICE_UNAVAILABLE.as_u16(),
+                detail: Some(detail.clone()),
+                instance: instance.clone(),
+                retry_after: None,
             },
         }
     }
@@ -65,7 +203,13 @@ impl fmt::Display for ApiError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::BadRequest { detail, .. }
-            | Self::RateLimited { detail, .. } => write!(f, "{detail}"),
+            | Self::Unauthorized { detail, .. }
+            | Self::Forbidden { detail, .. }
+            | Self::NotFound { detail, .. }
+            | Self::Conflict { detail, .. }
+            | Self::RateLimited { detail, .. }
+            | Self::Internal { detail, .. }
+            | Self::Unavailable { detail, .. } => write!(f, "{detail}"),
         }
     }
 }
@@ -74,12 +218,28 @@ impl ResponseError for ApiError {
     fn status_code(&self) -> StatusCode {
         match self {
             Self::BadRequest { .. } => StatusCode::BAD_REQUEST,
+            Self::Unauthorized { .. } => StatusCode::UNAUTHORIZED,
+            Self::Forbidden { .. } => StatusCode::FORBIDDEN,
+            Self::NotFound { .. } => StatusCode::NOT_FOUND,
+            Self::Conflict { .. } => StatusCode::CONFLICT,
             Self::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Self::Internal { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Unavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
         }
     }
 
     fn error_response(&self) -> HttpResponse {
-        HttpResponse::build(self.status_code())
+        let mut response = HttpResponse::build(self.status_code());
+        match self {
+            Self::Unauthorized { .. } => {
+                response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+            }
+            Self::RateLimited { retry_after, .. } => {
+                response.insert_header((header::RETRY_AFTER, whole_seconds(*retry_after)));
+            }
+            _ => {}
+        }
+        response
             .content_type("application/problem+json")
             .json(self.to_problem_details())
     }
@@ -1,55 +1,100 @@
-use std::collections::HashMap;
+use crate::constraints::{Constraints, ALPHABET_SIZE, WORD_LENGTH};
+use crate::models::Word;
+use crate::word_set::WordSet;
 
-use crate::models::{Color, Guess, Word};
-use rayon::prelude::*;
-
-pub fn filter_words_by_guesses(words: &[Word], guesses: &[Guess]) -> Vec<Word> {
-    words
-        .par_iter()
-        .filter(|word| filter_word_by_guesses(word, guesses))
-        .cloned()
-        .collect()
+/// Precomputed sets of dictionary words by letter position and letter count,
+/// so that filtering is a handful of set intersections.
+pub struct WordIndex {
+    capacity: usize,
+    // Indexed by `position * ALPHABET_SIZE + letter`.
+    at_position: Vec<WordSet>,
+    // Indexed by `letter * WORD_LENGTH + count - 1`, words with at least `count`
+    // of the letter.
+    at_least: Vec<WordSet>,
 }
 
-fn filter_word_by_guesses(word: &Word, guesses: &[Guess]) -> bool {
-    let word_chars = word.word.as_bytes();
-    let char_counts = word_chars.iter().fold(HashMap::new(), |mut acc, &c| {
-        *acc.entry(c).or_insert(0) += 1;
-        acc
-    });
-
-    guesses.iter().all(|guess| {
-        let expected_total = get_expected_total_of_letters(guesses, guess);
-        let actual_total = *char_counts.get(&(guess.letter as u8)).unwrap_or(&0);
-
-        match guess.color {
-            Color::Green => word_chars[guess.position] == guess.letter as u8,
-            Color::Yellow => {
-                actual_total >= expected_total && word_chars[guess.position] != guess.letter as u8
+impl WordIndex {
+    pub fn new(words: &[Word]) -> Self {
+        let capacity = words.len();
+        let mut at_position = vec![WordSet::empty(capacity); WORD_LENGTH * ALPHABET_SIZE];
+        let mut at_least = vec![WordSet::empty(capacity); ALPHABET_SIZE * WORD_LENGTH];
+
+        for (i, word) in words.iter().enumerate() {
+            let mut counts = [0usize; ALPHABET_SIZE];
+            for (position, &letter) in word.bytes.iter().enumerate() {
+                let letter = usize::from(letter - b'a');
+                at_position[position * ALPHABET_SIZE + letter].insert(i);
+                counts[letter] += 1;
             }
-            Color::Grey => {
-                expected_total == actual_total && word_chars[guess.position] != guess.letter as u8
+            for (letter, &count) in counts.iter().enumerate() {
+                for at_least_count in 1..=count {
+                    at_least[letter * WORD_LENGTH + at_least_count - 1].insert(i);
+                }
             }
         }
-    })
+
+        WordIndex {
+            capacity,
+            at_position,
+            at_least,
+        }
+    }
+
+    pub fn filter(&self, constraints: &Constraints) -> WordSet {
+        if !constraints.is_satisfiable() {
+            return WordSet::empty(self.capacity);
+        }
+
+        let mut candidates = WordSet::full(self.capacity);
+        for position in 0..WORD_LENGTH {
+            if let Some(letter) = constraints.green(position) {
+                candidates.intersect_with(self.words_with_letter_at(letter, position));
+            }
+            for letter in constraints.forbidden_letters(position) {
+                candidates.difference_with(self.words_with_letter_at(letter, position));
+            }
+        }
+
+        for letter in b'a'..=b'z' {
+            let (min, max) = constraints.count_bounds(letter);
+            if min > 0 {
+                candidates.intersect_with(self.words_with_at_least(letter, min));
+            }
+            if usize::from(max) < WORD_LENGTH {
+                candidates.difference_with(self.words_with_at_least(letter, max + 1));
+            }
+        }
+        candidates
+    }
+
+    fn words_with_letter_at(&self, letter: u8, position: usize) -> &WordSet {
+        &self.at_position[position * ALPHABET_SIZE + usize::from(letter - b'a')]
+    }
+
+    fn words_with_at_least(&self, letter: u8, count: u8) -> &WordSet {
+        &self.at_least[usize::from(letter - b'a') * WORD_LENGTH + usize::from(count) - 1]
+    }
 }
 
-fn get_expected_total_of_letters(guesses: &[Guess], guess: &Guess) -> usize {
-    guesses
-        .iter()
-        .filter(|g| g.turn == guess.turn && g.letter == guess.letter)
-        .fold(0, |acc, g| match g.color {
-            Color::Green | Color::Yellow => acc + 1,
-            Color::Grey => acc,
-        })
+pub fn filter_words<'a>(
+    words: &'a [Word],
+    index: &WordIndex,
+    constraints: &Constraints,
+) -> Vec<&'a Word> {
+    select_words(words, &index.filter(constraints))
+}
+
+pub fn select_words<'a>(words: &'a [Word], candidates: &WordSet) -> Vec<&'a Word> {
+    candidates.iter().map(|i| &words[i]).collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::{Color, Guess};
 
     #[test]
-    fn test_filter_words_by_guesses() {
+    fn test_filter_words() {
         // Given
         let guesses = [
             Guess {
@@ -95,10 +140,14 @@ mod tests {
         ];
 
         // When
-        let actual_words = filter_words_by_guesses(&input_words, &guesses);
+        let index = WordIndex::new(&input_words);
+        let actual_words = filter_words(&input_words, &index, &Constraints::from_guesses(&guesses));
 
         // Then
-        assert_eq!(actual_words, expected_words);
+        assert_eq!(
+            actual_words.into_iter().cloned().collect::<Vec<_>>(),
+            expected_words
+        );
     }
 
     #[test]
@@ -125,10 +174,14 @@ mod tests {
         let expected_words = vec![Word::new("tenet".to_string(), true)];
 
         // When
-        let actual_words = filter_words_by_guesses(&input_words, &guesses);
+        let index = WordIndex::new(&input_words);
+        let actual_words = filter_words(&input_words, &index, &Constraints::from_guesses(&guesses));
 
         // Then
-        assert_eq!(actual_words, expected_words);
+        assert_eq!(
+            actual_words.into_iter().cloned().collect::<Vec<_>>(),
+            expected_words
+        );
     }
 
     #[test]
@@ -173,9 +226,82 @@ mod tests {
         let expected_words = vec![Word::new("asset".to_string(), true)];
 
         // When
-        let actual_words = filter_words_by_guesses(&input_words, &guesses);
+        let index = WordIndex::new(&input_words);
+        let actual_words = filter_words(&input_words, &index, &Constraints::from_guesses(&guesses));
 
         // Then
-        assert_eq!(actual_words, expected_words);
+        assert_eq!(
+            actual_words.into_iter().cloned().collect::<Vec<_>>(),
+            expected_words
+        );
+    }
+
+    #[test]
+    fn test_word_index_agrees_with_constraints() {
+        // Given
+        let input_words: Vec<Word> = [
+            "geese", "eyrie", "theme", "emcee", "crane", "caret", "trace",
+        ]
+        .iter()
+        .map(|w| Word::new(w.to_string(), true))
+        .collect();
+        let index = WordIndex::new(&input_words);
+        let turns = [
+            (
+                "geese",
+                [
+                    Color::Grey,
+                    Color::Yellow,
+                    Color::Grey,
+                    Color::Grey,
+                    Color::Green,
+                ],
+            ),
+            (
+                "trace",
+                [
+                    Color::Yellow,
+                    Color::Yellow,
+                    Color::Grey,
+                    Color::Yellow,
+                    Color::Yellow,
+                ],
+            ),
+            (
+                "crane",
+                [
+                    Color::Green,
+                    Color::Grey,
+                    Color::Grey,
+                    Color::Grey,
+                    Color::Grey,
+                ],
+            ),
+        ];
+
+        for (word, colors) in turns {
+            let guesses: Vec<Guess> = word
+                .chars()
+                .zip(colors)
+                .enumerate()
+                .map(|(position, (letter, color))| Guess {
+                    turn: 0,
+                    letter,
+                    position,
+                    color,
+                })
+                .collect();
+            let constraints = Constraints::from_guesses(&guesses);
+
+            // When
+            let actual_words = filter_words(&input_words, &index, &constraints);
+
+            // Then
+            let expected_words: Vec<&Word> = input_words
+                .iter()
+                .filter(|w| constraints.matches(&w.bytes))
+                .collect();
+            assert_eq!(actual_words, expected_words, "guess {word}");
+        }
     }
 }
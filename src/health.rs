@@ -0,0 +1,145 @@
+use actix_web::{get, web, HttpResponse};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::state::AppState;
+
+#[derive(Serialize, ToSchema)]
+pub struct Health {
+    #[schema(example = "ok")]
+    pub status: &'static str,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Readiness {
+    /// `ready` once every check passes, `starting` until the server has
+    /// started, and `busy` while it has no room to rank more.
+    #[schema(example = "ready")]
+    pub status: &'static str,
+    pub checks: ReadinessChecks,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReadinessChecks {
+    /// The answer and guess lists were read and are not empty.
+    pub word_lists: bool,
+    /// The opening guesses have been ranked.
+    pub opening_guesses: bool,
+    /// The compute pool can take another ranking.
+    pub compute: bool,
+}
+
+impl ReadinessChecks {
+    fn started(&self) -> bool {
+        self.word_lists && self.opening_guesses
+    }
+
+    fn passed(&self) -> bool {
+        self.started() && self.compute
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "health",
+    responses((status = 200, description = "The process is up", body = Health))
+)]
+#[get("/healthz")]
+pub async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().json(Health { status: "ok" })
+}
+
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    responses(
+        (status = 200, description = "Ready for traffic", body = Readiness),
+        (status = 503, description = "Still starting up, or too busy to rank more", body = Readiness),
+    )
+)]
+#[get("/readyz")]
+pub async fn readyz(state: web::Data<AppState>) -> HttpResponse {
+    let checks = ReadinessChecks {
+        word_lists: !state.words.is_empty(),
+        opening_guesses: state.is_warm(),
+        compute: !state.compute.is_saturated(),
+    };
+    if checks.passed() {
+        HttpResponse::Ok().json(Readiness {
+            status: "ready",
+            checks,
+        })
+    } else {
+        let status = if checks.started() { "busy" } else { "starting" };
+        HttpResponse::ServiceUnavailable().json(Readiness { status, checks })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::test_state;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::App;
+    use std::sync::mpsc;
+
+    fn state() -> web::Data<AppState> {
+        web::Data::new(test_state(&["crane", "slate", "aloof"], usize::MAX))
+    }
+
+    #[actix_web::test]
+    async fn test_ready_only_once_the_opening_guesses_are_ranked() {
+        // Given
+        let state = state();
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .service(healthz)
+                .service(readyz),
+        )
+        .await;
+
+        // When
+        let healthy =
+            test::call_service(&app, TestRequest::get().uri("/healthz").to_request()).await;
+        let starting =
+            test::call_service(&app, TestRequest::get().uri("/readyz").to_request()).await;
+        state.warm_up();
+        let ready = test::call_service(&app, TestRequest::get().uri("/readyz").to_request()).await;
+
+        // Then
+        assert_eq!(healthy.status(), StatusCode::OK);
+        assert_eq!(starting.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = test::read_body_json(starting).await;
+        assert_eq!(body["checks"]["word_lists"], true);
+        assert_eq!(body["checks"]["opening_guesses"], false);
+        assert_eq!(ready.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_not_ready_while_the_compute_pool_is_full() {
+        // Given
+        let state = state();
+        state.warm_up();
+        let app = test::init_service(App::new().app_data(state.clone()).service(readyz)).await;
+        let (release, blocked) = mpsc::channel::<()>();
+        let busy = state.compute.run(move || blocked.recv().unwrap());
+
+        // When
+        let saturated =
+            test::call_service(&app, TestRequest::get().uri("/readyz").to_request()).await;
+        release.send(()).unwrap();
+        busy.await.unwrap();
+        let ready = test::call_service(&app, TestRequest::get().uri("/readyz").to_request()).await;
+
+        // Then
+        assert_eq!(saturated.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = test::read_body_json(saturated).await;
+        assert_eq!(body["status"], "busy");
+        assert_eq!(body["checks"]["compute"], false);
+        assert_eq!(ready.status(), StatusCode::OK);
+    }
+}
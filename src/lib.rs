@@ -0,0 +1,14 @@
+//! Wordle solving without the web server: the word and guess models, pattern
+//! computation, filtering by constraints and entropy scoring.
+
+pub mod constraints;
+pub mod dictionary;
+pub mod entropy;
+pub mod filters;
+pub mod models;
+pub mod word_set;
+
+pub use constraints::Constraints;
+pub use entropy::{calculate_entropy_for_words, calculate_entropy_within, compute_pattern};
+pub use filters::{filter_words, WordIndex};
+pub use models::{Color, Guess, Word};
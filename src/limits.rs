@@ -0,0 +1,379 @@
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{Error, HttpMessage, HttpRequest, ResponseError};
+use ipnet::IpNet;
+use parking_lot::Mutex;
+use std::future::{ready, Future, Ready};
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::api_keys::ApiKeyStore;
+use crate::client_ip::ClientIpResolver;
+use crate::errors::ApiError;
+use crate::logging;
+use crate::metrics::Metrics;
+use crate::rate_limit::{whole_seconds, IpRateLimiter, RateLimitDecision};
+
+/// What every rate limited scope shares: who sent a request, and the addresses
+/// that are never limited or always turned away.
+pub struct Limits {
+    client_ips: ClientIpResolver,
+    api_keys: ApiKeyStore,
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+    metrics: Arc<Metrics>,
+    // Every policy's limiter by scope, for `/metrics`.
+    policies: Mutex<Vec<(&'static str, Arc<IpRateLimiter>)>>,
+}
+
+impl Limits {
+    pub fn new(
+        client_ips: ClientIpResolver,
+        api_keys: ApiKeyStore,
+        allow: Vec<IpNet>,
+        deny: Vec<IpNet>,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        Self {
+            client_ips,
+            api_keys,
+            allow,
+            deny,
+            metrics,
+            policies: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The address `req` came from, looking through trusted proxies.
+    pub fn client_ip(&self, req: &HttpRequest) -> IpAddr {
+        self.client_ips.client(req)
+    }
+
+    pub fn policies(&self) -> Vec<(&'static str, Arc<IpRateLimiter>)> {
+        self.policies.lock().clone()
+    }
+}
+
+/// Middleware charging every request to a scope one token, and reporting the
+/// client's quota in `RateLimit-*` headers. Clones share their buckets, so one
+/// policy can cover several scopes.
+#[derive(Clone)]
+pub struct RateLimit {
+    scope: &'static str,
+    limits: Arc<Limits>,
+    limiter: Arc<IpRateLimiter>,
+}
+
+impl RateLimit {
+    /// A policy named `scope` in metrics.
+    pub fn new(scope: &'static str, limits: Arc<Limits>, limiter: IpRateLimiter) -> Self {
+        let limiter = Arc::new(limiter);
+        limits.policies.lock().push((scope, Arc::clone(&limiter)));
+        Self {
+            scope,
+            limits,
+            limiter,
+        }
+    }
+
+    fn admit(&self, req: &HttpRequest) -> Result<Payer, ApiError> {
+        let admitted = self.try_admit(req);
+        self.count_rejection(&admitted);
+        admitted
+    }
+
+    fn try_admit(&self, req: &HttpRequest) -> Result<Payer, ApiError> {
+        let client = self.limits.client_ip(req);
+        if self
+            .limits
+            .deny
+            .iter()
+            .any(|network| network.contains(&client))
+        {
+            return Err(ApiError::forbidden(
+                "Requests from this address are not allowed.",
+                logging::instance(req),
+            ));
+        }
+        if self
+            .limits
+            .allow
+            .iter()
+            .any(|network| network.contains(&client))
+        {
+            return Ok(Payer::Exempt);
+        }
+
+        match self.limits.api_keys.check(req, 1)? {
+            Some((key, decision)) => {
+                record(req, decision)?;
+                Ok(Payer::Key(key))
+            }
+            None => {
+                let ip = self.limits.client_ips.rate_limit_key(client);
+                record(req, self.limiter.check(ip))?;
+                Ok(Payer::Address(ip))
+            }
+        }
+    }
+
+    fn charge(
+        &self,
+        req: &HttpRequest,
+        payer: &Payer,
+        cost: u32,
+        paid: u32,
+    ) -> Result<(), ApiError> {
+        let decision = match payer {
+            Payer::Exempt => return Ok(()),
+            Payer::Key(key) => self.limits.api_keys.charge(key, cost, paid),
+            Payer::Address(ip) => self.limiter.check_more(*ip, cost, paid),
+        };
+        let charged = record(req, decision);
+        self.count_rejection(&charged);
+        charged
+    }
+
+    fn count_rejection<T>(&self, result: &Result<T, ApiError>) {
+        if let Err(ApiError::RateLimited { .. }) = result {
+            self.limits.metrics.observe_rate_limited(self.scope);
+        }
+    }
+}
+
+/// Charges the client `cost` more tokens for work a handler is about to do,
+/// on top of the one `RateLimit` took when the request came in. The request
+/// is admitted just as if it had been charged for everything up front.
+pub fn charge(req: &HttpRequest, cost: u32) -> Result<(), ApiError> {
+    charge_account(req, cost, 1)
+}
+
+/// Charges a message on a connection `RateLimit` admitted `cost` tokens, with
+/// nothing paid up front.
+pub fn charge_message(req: &HttpRequest, cost: u32) -> Result<(), ApiError> {
+    charge_account(req, cost, 0)
+}
+
+fn charge_account(req: &HttpRequest, cost: u32, paid: u32) -> Result<(), ApiError> {
+    if cost == 0 {
+        return Ok(());
+    }
+    let account = req.extensions().get::<Account>().cloned();
+    match account {
+        Some(account) => account.policy.charge(req, &account.payer, cost, paid),
+        None => Ok(()),
+    }
+}
+
+// Who a request was admitted as, so that handlers can charge the same bucket.
+#[derive(Clone)]
+struct Account {
+    policy: RateLimit,
+    payer: Payer,
+}
+
+#[derive(Clone)]
+enum Payer {
+    Exempt,
+    Key(String),
+    Address(IpAddr),
+}
+
+// Keeps the decision for the response headers, and turns a refusal into a 429.
+fn record(req: &HttpRequest, decision: RateLimitDecision) -> Result<(), ApiError> {
+    req.extensions_mut().insert(decision);
+    if !decision.allowed {
+        return Err(ApiError::rate_limited(
+            format!(
+                "Rate limit exceeded. Try again in {}s.",
+                whole_seconds(decision.retry_after)
+            ),
+            logging::instance(req),
+            decision.retry_after,
+        ));
+    }
+    Ok(())
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service,
+            policy: self.clone(),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: S,
+    policy: RateLimit,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let payer = match self.policy.admit(req.request()) {
+            Ok(payer) => payer,
+            Err(err) => {
+                let res = req
+                    .into_response(err.error_response())
+                    .map_into_right_body();
+                return Box::pin(ready(Ok(with_rate_limit_headers(res))));
+            }
+        };
+        req.extensions_mut().insert(Account {
+            policy: self.policy.clone(),
+            payer,
+        });
+
+        let res = self.service.call(req);
+        Box::pin(async move { Ok(with_rate_limit_headers(res.await?.map_into_left_body())) })
+    }
+}
+
+// Handlers may have charged more since the request was admitted, so the
+// headers report the last decision rather than the first.
+fn with_rate_limit_headers<B>(mut res: ServiceResponse<B>) -> ServiceResponse<B> {
+    let decision = res
+        .request()
+        .extensions()
+        .get::<RateLimitDecision>()
+        .copied();
+    if let Some(decision) = decision {
+        decision.insert_headers(res.headers_mut());
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client_ip::ForwardedHeader;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+    use std::net::SocketAddr;
+    use std::num::NonZeroUsize;
+
+    fn policy(max_requests: u32, allow: &[&str], deny: &[&str]) -> RateLimit {
+        let networks = |list: &[&str]| list.iter().map(|n| n.parse().unwrap()).collect();
+        let limits = Limits::new(
+            ClientIpResolver::new(Vec::new(), ForwardedHeader::default(), None),
+            ApiKeyStore::empty(),
+            networks(allow),
+            networks(deny),
+            Arc::new(Metrics::new()),
+        );
+        let limiter = IpRateLimiter::new(max_requests, 60.0, NonZeroUsize::new(10).unwrap());
+        RateLimit::new("test", Arc::new(limits), limiter)
+    }
+
+    fn from(ip: &str) -> TestRequest {
+        TestRequest::get()
+            .uri("/limited")
+            .peer_addr(SocketAddr::new(ip.parse().unwrap(), 40000))
+    }
+
+    #[actix_web::test]
+    async fn test_only_wrapped_scopes_are_limited() {
+        // Given
+        let app = test::init_service(
+            App::new()
+                .service(
+                    web::scope("/limited")
+                        .wrap(policy(1, &[], &[]))
+                        .route("", web::get().to(HttpResponse::Ok)),
+                )
+                .route("/healthz", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        // When
+        let first = test::call_service(&app, from("192.0.2.1").to_request()).await;
+        let second = test::call_service(&app, from("192.0.2.1").to_request()).await;
+        let health = test::call_service(&app, from("192.0.2.1").uri("/healthz").to_request()).await;
+
+        // Then
+        assert_eq!(first.status(), StatusCode::OK);
+        assert_eq!(first.headers().get("ratelimit-remaining").unwrap(), "0");
+        assert_eq!(second.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(second.headers().contains_key("retry-after"));
+        assert_eq!(health.status(), StatusCode::OK);
+        assert!(!health.headers().contains_key("ratelimit-limit"));
+    }
+
+    #[actix_web::test]
+    async fn test_allowed_addresses_are_never_limited_and_denied_ones_never_served() {
+        // Given
+        let app = test::init_service(
+            App::new().service(
+                web::scope("/limited")
+                    .wrap(policy(1, &["10.0.0.0/8"], &["198.51.100.0/24"]))
+                    .route("", web::get().to(HttpResponse::Ok)),
+            ),
+        )
+        .await;
+
+        // When
+        let mut allowed = Vec::new();
+        for _ in 0..3 {
+            allowed.push(test::call_service(&app, from("10.1.2.3").to_request()).await);
+        }
+        let denied = test::call_service(&app, from("198.51.100.7").to_request()).await;
+
+        // Then
+        assert!(allowed.iter().all(|res| res.status() == StatusCode::OK));
+        assert_eq!(denied.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn test_handlers_charge_the_bucket_the_request_was_admitted_from() {
+        // Given
+        let expensive =
+            |req: HttpRequest| async move { charge(&req, 2).map(|()| HttpResponse::Ok().finish()) };
+        let shared = policy(3, &[], &[]);
+        let app = test::init_service(
+            App::new()
+                .service(
+                    web::scope("/limited")
+                        .wrap(shared.clone())
+                        .route("", web::get().to(expensive)),
+                )
+                .service(
+                    web::scope("/other")
+                        .wrap(shared)
+                        .route("", web::get().to(HttpResponse::Ok)),
+                ),
+        )
+        .await;
+
+        // When
+        let res = test::call_service(&app, from("192.0.2.1").to_request()).await;
+        let other = test::call_service(&app, from("192.0.2.1").uri("/other").to_request()).await;
+
+        // Then
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("ratelimit-remaining").unwrap(), "0");
+        assert_eq!(other.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
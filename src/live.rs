@@ -0,0 +1,512 @@
+use actix_web::rt::time::timeout;
+use actix_web::{get, rt, web, HttpRequest, HttpResponse};
+use actix_ws::{AggregatedMessage, Session};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::errors::{ApiError, ProblemDetails};
+use crate::rate_limit::{whole_seconds, RateLimiter};
+use crate::state::AppState;
+use crate::{limits, logging};
+use wordle_solver::constraints::Constraints;
+use wordle_solver::models::{Guess, WordListPage, WordListQuery};
+
+const DEFAULT_SUGGESTIONS: usize = 10;
+const MAX_MESSAGE_SIZE: usize = 4 * 1024;
+const IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// How many messages each connection may send, apart from the tokens their
+/// scoring costs the client.
+#[derive(Clone, Copy)]
+pub struct MessageAllowance {
+    pub max_messages: u32,
+    pub window_seconds: f64,
+}
+
+impl MessageAllowance {
+    fn limiter(&self) -> RateLimiter<()> {
+        RateLimiter::new(self.max_messages, self.window_seconds, NonZeroUsize::MIN)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    SetTile(Guess),
+    ClearTile { turn: usize, position: usize },
+    Reset,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage<'a> {
+    Update(WordListPage<'a>),
+    Error { detail: String },
+}
+
+// The tiles coloured so far, which need not make up whole words while the
+// user is still clicking through a row.
+#[derive(Default)]
+struct Board {
+    tiles: BTreeMap<(usize, usize), Guess>,
+}
+
+impl Board {
+    fn apply(&mut self, message: ClientMessage) -> Result<(), String> {
+        match message {
+            ClientMessage::SetTile(mut guess) => {
+                guess.normalise()?;
+                self.tiles.insert((guess.turn, guess.position), guess);
+            }
+            ClientMessage::ClearTile { turn, position } => {
+                self.tiles.remove(&(turn, position));
+            }
+            ClientMessage::Reset => self.tiles.clear(),
+        }
+        Ok(())
+    }
+
+    fn constraints(&self) -> Constraints {
+        let guesses: Vec<Guess> = self.tiles.values().cloned().collect();
+        Constraints::from_guesses(&guesses)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/live",
+    tag = "solver",
+    params(WordListQuery),
+    responses(
+        (status = 101, description = "Switches to a WebSocket. Send `set_tile`, `clear_tile` or `reset` messages and receive an `update` with the remaining words after each one."),
+        (status = 400, description = "Not a WebSocket handshake", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or unknown API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Disabled API key or denied address", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+#[get("")]
+pub async fn live_solver(
+    state: web::Data<AppState>,
+    allowance: web::Data<MessageAllowance>,
+    query: web::Query<WordListQuery>,
+    req: HttpRequest,
+    body: web::Payload,
+) -> Result<HttpResponse, ApiError> {
+    let (response, session, stream) = actix_ws::handle(&req, body)
+        .map_err(|err| ApiError::bad_request(err.to_string(), logging::instance(&req)))?;
+
+    let mut query = query.into_inner();
+    query.limit.get_or_insert(DEFAULT_SUGGESTIONS);
+
+    let stream = stream
+        .max_frame_size(MAX_MESSAGE_SIZE)
+        .aggregate_continuations()
+        .max_continuation_size(MAX_MESSAGE_SIZE);
+    rt::spawn(run_session(
+        state.into_inner(),
+        req.clone(),
+        allowance.limiter(),
+        query,
+        session,
+        stream,
+        IDLE_TIMEOUT,
+    ));
+
+    Ok(response)
+}
+
+async fn run_session(
+    state: Arc<AppState>,
+    // Kept to charge each update to the client that opened the socket.
+    req: HttpRequest,
+    messages: RateLimiter<()>,
+    query: WordListQuery,
+    mut session: Session,
+    mut stream: actix_ws::AggregatedMessageStream,
+    idle_timeout: Duration,
+) {
+    let mut board = Board::default();
+
+    while let Ok(Some(Ok(message))) = timeout(idle_timeout, stream.recv()).await {
+        let sent = match message {
+            AggregatedMessage::Text(text) => {
+                let reply =
+                    handle_message(&state, &req, &messages, &query, &mut board, &text).await;
+                session.text(reply).await
+            }
+            AggregatedMessage::Ping(bytes) => session.pong(&bytes).await,
+            AggregatedMessage::Close(_) => break,
+            AggregatedMessage::Binary(_) | AggregatedMessage::Pong(_) => Ok(()),
+        };
+        if sent.is_err() {
+            return;
+        }
+    }
+
+    let _ = session.close(None).await;
+}
+
+async fn handle_message(
+    state: &Arc<AppState>,
+    req: &HttpRequest,
+    messages: &RateLimiter<()>,
+    query: &WordListQuery,
+    board: &mut Board,
+    text: &str,
+) -> String {
+    let applied = serde_json::from_str::<ClientMessage>(text)
+        .map_err(|err| err.to_string())
+        .and_then(|message| board.apply(message));
+
+    let constraints = board.constraints();
+    let candidates = state.word_index.filter(&constraints);
+    let ranked = match applied.and_then(|()| take_message(messages)) {
+        Ok(()) => state
+            .possible_words_for(req, &constraints, &candidates, |cost| {
+                limits::charge_message(req, cost)
+            })
+            .await
+            .map_err(|err| err.to_string()),
+        Err(detail) => Err(detail),
+    };
+    let possible_words = match ranked {
+        Ok(possible_words) => possible_words,
+        Err(detail) => {
+            return serde_json::to_string(&ServerMessage::Error { detail }).unwrap_or_default()
+        }
+    };
+    serde_json::to_string(&ServerMessage::Update(possible_words.select(query))).unwrap_or_default()
+}
+
+// Clicking through tiles sends a message apiece, so messages come out of the
+// connection's own allowance rather than the request limit. Only the upgrade
+// went through the middleware, so scoring is charged to the client separately.
+fn take_message(messages: &RateLimiter<()>) -> Result<(), String> {
+    let decision = messages.check(());
+    if !decision.allowed {
+        return Err(format!(
+            "Too many messages. Try again in {}s.",
+            whole_seconds(decision.retry_after)
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_keys::ApiKeyStore;
+    use crate::client_ip::{ClientIpResolver, ForwardedHeader};
+    use crate::limits::{Limits, RateLimit};
+    use crate::metrics::Metrics;
+    use crate::rate_limit::IpRateLimiter;
+    use crate::state::test_state;
+    use actix_web::dev::Payload;
+    use actix_web::error::PayloadError;
+    use actix_web::http::{header, StatusCode};
+    use actix_web::test::{self, TestRequest};
+    use actix_web::web::Bytes;
+    use actix_web::App;
+    use futures_channel::mpsc;
+    use futures_util::Stream;
+    use std::net::SocketAddr;
+    use std::pin::Pin;
+    use wordle_solver::models::Color;
+
+    fn state() -> web::Data<AppState> {
+        web::Data::new(test_state(&["crane", "slate", "aloof"], usize::MAX))
+    }
+
+    #[test]
+    fn test_board_tracks_partial_rows() {
+        // Given
+        let mut board = Board::default();
+        let messages = [
+            r#"{"type":"set_tile","turn":0,"position":0,"letter":"C","color":"Green"}"#,
+            r#"{"type":"set_tile","turn":0,"position":1,"letter":"r","color":"Grey"}"#,
+            r#"{"type":"clear_tile","turn":0,"position":1}"#,
+        ];
+
+        // When
+        for message in messages {
+            board.apply(serde_json::from_str(message).unwrap()).unwrap();
+        }
+
+        // Then
+        let expected = Constraints::from_guesses(&[Guess {
+            turn: 0,
+            letter: 'c',
+            position: 0,
+            color: Color::Green,
+        }]);
+        assert_eq!(board.constraints(), expected);
+    }
+
+    #[test]
+    fn test_board_rejects_invalid_tiles() {
+        // Given
+        let mut board = Board::default();
+        let message = r#"{"type":"set_tile","turn":0,"position":7,"letter":"c","color":"Green"}"#;
+
+        // When
+        let result = board.apply(serde_json::from_str(message).unwrap());
+
+        // Then
+        assert_eq!(
+            result.err(),
+            Some("Guess positions must be between 0 and 4.".to_string())
+        );
+        assert_eq!(board.constraints(), Constraints::default());
+    }
+
+    #[actix_web::test]
+    async fn test_tile_messages_are_not_held_to_the_request_limit() {
+        // Given
+        let state = state();
+        let limits = Limits::new(
+            ClientIpResolver::new(Vec::new(), ForwardedHeader::default(), None),
+            ApiKeyStore::empty(),
+            Vec::new(),
+            Vec::new(),
+            Arc::new(Metrics::new()),
+        );
+        let one_request_a_minute = RateLimit::new(
+            "suggestions",
+            Arc::new(limits),
+            IpRateLimiter::new(1, 60.0, NonZeroUsize::MIN),
+        );
+        let app = test::init_service(App::new().app_data(state).service(
+            web::scope("/live").wrap(one_request_a_minute).route(
+                "",
+                web::get().to(|state: web::Data<AppState>, req: HttpRequest| async move {
+                    let state = state.into_inner();
+                    let messages = MessageAllowance {
+                        max_messages: 3,
+                        window_seconds: 60.0,
+                    }
+                    .limiter();
+                    let mut board = Board::default();
+                    let mut replies = Vec::new();
+                    for position in 0..4 {
+                        let text = format!(
+                            r#"{{"type":"set_tile","turn":0,"position":{position},"letter":"z","color":"Grey"}}"#
+                        );
+                        let query = WordListQuery::default();
+                        replies.push(
+                            handle_message(&state, &req, &messages, &query, &mut board, &text)
+                                .await,
+                        );
+                    }
+                    HttpResponse::Ok().json(replies)
+                }),
+            ),
+        ))
+        .await;
+
+        // When
+        let res = test::call_service(
+            &app,
+            TestRequest::get()
+                .uri("/live")
+                .peer_addr(SocketAddr::new("192.0.2.1".parse().unwrap(), 40000))
+                .to_request(),
+        )
+        .await;
+
+        // Then
+        assert_eq!(res.status(), StatusCode::OK);
+        let replies: Vec<String> = test::read_body_json(res).await;
+        let types: Vec<String> = replies
+            .iter()
+            .map(|reply| {
+                let reply: serde_json::Value = serde_json::from_str(reply).unwrap();
+                reply["type"].as_str().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(types, ["update", "update", "update", "error"]);
+        assert!(replies[3].contains("Too many messages"), "{}", replies[3]);
+    }
+
+    // Client frames as a browser would send them, masked with a zero key so
+    // the payload stays readable.
+    fn client_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0x80 | opcode, 0x80 | payload.len() as u8, 0, 0, 0, 0];
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    // The opcode and payload of each frame the server sent.
+    fn server_frames(mut bytes: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut frames = Vec::new();
+        while let [first, second, rest @ ..] = bytes {
+            let (len, rest) = match second & 0x7f {
+                126 => (
+                    usize::from(u16::from_be_bytes([rest[0], rest[1]])),
+                    &rest[2..],
+                ),
+                len => (usize::from(len), rest),
+            };
+            frames.push((first & 0x0f, rest[..len].to_vec()));
+            bytes = &rest[len..];
+        }
+        frames
+    }
+
+    fn handshake(uri: &str) -> TestRequest {
+        TestRequest::get()
+            .uri(uri)
+            .insert_header((header::UPGRADE, "websocket"))
+            .insert_header((header::CONNECTION, "upgrade"))
+            .insert_header((header::SEC_WEBSOCKET_VERSION, "13"))
+            .insert_header((header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ=="))
+    }
+
+    // Opens a socket whose session gives up after `idle_timeout`, with frames
+    // from the client sent down the returned channel as the test goes.
+    async fn open_socket(
+        idle_timeout: Duration,
+    ) -> (
+        actix_web::dev::ServiceResponse,
+        mpsc::UnboundedSender<Result<Bytes, PayloadError>>,
+    ) {
+        let app = test::init_service(App::new().app_data(state()).route(
+            "/live",
+            web::get().to(
+                move |state: web::Data<AppState>, req: HttpRequest, body: web::Payload| async move {
+                    let (response, session, stream) = actix_ws::handle(&req, body).unwrap();
+                    let messages = MessageAllowance {
+                        max_messages: 10,
+                        window_seconds: 1.0,
+                    }
+                    .limiter();
+                    rt::spawn(run_session(
+                        state.into_inner(),
+                        req,
+                        messages,
+                        WordListQuery::default(),
+                        session,
+                        stream.aggregate_continuations(),
+                        idle_timeout,
+                    ));
+                    response
+                },
+            ),
+        ))
+        .await;
+        let (frames, payload) = mpsc::unbounded();
+        let payload: Pin<Box<dyn Stream<Item = _>>> = Box::pin(payload);
+        let (req, _) = handshake("/live")
+            .to_request()
+            .replace_payload(Payload::from(payload));
+        (test::call_service(&app, req).await, frames)
+    }
+
+    #[actix_web::test]
+    async fn test_idle_sockets_are_closed() {
+        // Given
+        let (res, _frames) = open_socket(Duration::from_millis(10)).await;
+
+        // When
+        let body = test::read_body(res).await;
+
+        // Then
+        let opcodes: Vec<u8> = server_frames(&body)
+            .iter()
+            .map(|(opcode, _)| *opcode)
+            .collect();
+        assert_eq!(opcodes, [0x8]);
+    }
+
+    #[actix_web::test]
+    async fn test_sessions_stop_once_the_client_has_gone() {
+        // Given
+        let (res, frames) = open_socket(Duration::from_secs(60)).await;
+        drop(res);
+
+        // When
+        frames
+            .unbounded_send(Ok(Bytes::from(client_frame(0x1, br#"{"type":"reset"}"#))))
+            .unwrap();
+
+        // Then
+        for _ in 0..100 {
+            if frames.is_closed() {
+                return;
+            }
+            rt::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("The session kept reading after its reply could not be sent");
+    }
+
+    #[actix_web::test]
+    async fn test_live_solver_answers_each_message_over_the_socket() {
+        // Given
+        let state = state();
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .app_data(web::Data::new(MessageAllowance {
+                    max_messages: 10,
+                    window_seconds: 1.0,
+                }))
+                .service(web::scope("/live").service(live_solver)),
+        )
+        .await;
+        let set_tile = br#"{"type":"set_tile","turn":0,"position":0,"letter":"s","color":"Green"}"#;
+        let mut payload = client_frame(0x1, set_tile);
+        payload.extend(client_frame(0x2, b"binary"));
+        payload.extend(client_frame(0x9, b"ping"));
+        payload.extend(client_frame(0xa, b"pong"));
+        payload.extend(client_frame(0x1, b"not json"));
+        payload.extend(client_frame(0x8, b""));
+
+        // When
+        let res = test::call_service(
+            &app,
+            handshake("/live?fields=word")
+                .set_payload(payload)
+                .to_request(),
+        )
+        .await;
+
+        // Then
+        assert_eq!(res.status(), StatusCode::SWITCHING_PROTOCOLS);
+        let frames = server_frames(&test::read_body(res).await);
+        let opcodes: Vec<u8> = frames.iter().map(|(opcode, _)| *opcode).collect();
+        assert_eq!(opcodes, [0x1, 0xa, 0x1, 0x8]);
+        let update: serde_json::Value = serde_json::from_slice(&frames[0].1).unwrap();
+        assert_eq!(update["type"], "update");
+        assert_eq!(
+            update["word_list"],
+            serde_json::json!([{ "word": "slate" }])
+        );
+        assert_eq!(frames[1].1, b"ping");
+        let error: serde_json::Value = serde_json::from_slice(&frames[2].1).unwrap();
+        assert_eq!(error["type"], "error");
+    }
+
+    #[actix_web::test]
+    async fn test_live_solver_needs_a_websocket_handshake() {
+        // Given
+        let app = test::init_service(
+            App::new()
+                .app_data(state())
+                .app_data(web::Data::new(MessageAllowance {
+                    max_messages: 10,
+                    window_seconds: 1.0,
+                }))
+                .service(web::scope("/live").service(live_solver)),
+        )
+        .await;
+
+        // When
+        let res = test::call_service(&app, TestRequest::get().uri("/live").to_request()).await;
+
+        // Then
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,292 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage, HttpRequest};
+use log::kv::{self, Key, Value, VisitSource, VisitValue};
+use log::{info, Record};
+use serde_json::{Map, Number};
+use std::io::{self, Write};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use crate::limits::Limits;
+
+pub const REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+// Probes hit these every few seconds and would drown out everything else.
+const UNLOGGED_PATHS: [&str; 2] = ["/healthz", "/readyz"];
+
+/// The id `log_requests` gave a request, sent back in `X-Request-Id`.
+#[derive(Clone, Copy)]
+pub struct RequestId(pub Uuid);
+
+// What ranking cost a request, for its log line.
+#[derive(Clone, Copy)]
+struct Ranking {
+    candidates: usize,
+    compute: Duration,
+}
+
+/// The `instance` of a problem with `req`: its request id, so that a
+/// reported error can be found in the logs. Requests that never went through
+/// `log_requests`, as in tests, fall back to the path.
+pub fn instance(req: &HttpRequest) -> String {
+    match req.extensions().get::<RequestId>() {
+        Some(RequestId(id)) => id.urn().to_string(),
+        None => req.path().to_string(),
+    }
+}
+
+/// Notes for the request's log line that it ranked `candidates` words, taking
+/// `compute`.
+pub fn record_ranking(req: &HttpRequest, candidates: usize, compute: Duration) {
+    req.extensions_mut().insert(Ranking {
+        candidates,
+        compute,
+    });
+}
+
+/// Gives every request an id and logs one line for it once the response
+/// headers are ready.
+pub async fn log_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let started = Instant::now();
+    let id = Uuid::new_v4();
+    req.extensions_mut().insert(RequestId(id));
+    let mut res = next.call(req).await?;
+
+    if let Ok(value) = HeaderValue::from_str(&id.to_string()) {
+        res.headers_mut().insert(REQUEST_ID, value);
+    }
+    let req = res.request();
+    if UNLOGGED_PATHS.contains(&req.path()) {
+        return Ok(res);
+    }
+
+    let client_ip = req
+        .app_data::<web::Data<Limits>>()
+        .map(|limits| limits.client_ip(req).to_string());
+    let client_ip = client_ip.as_deref();
+    let ranking = req.extensions().get::<Ranking>().copied();
+    info!(
+        request_id:% = id,
+        method = req.method().as_str(),
+        path = req.path(),
+        status = res.status().as_u16(),
+        duration_ms = millis(started.elapsed()),
+        client_ip = client_ip,
+        candidates = ranking.map(|ranking| ranking.candidates),
+        compute_ms = ranking.map(|ranking| millis(ranking.compute));
+        "{} {} {}",
+        req.method(),
+        req.path(),
+        res.status().as_u16()
+    );
+    Ok(res)
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Writes `record` as a single line of JSON, with its key-values as fields
+/// of their own next to the message.
+pub fn format_json(buf: &mut impl Write, record: &Record) -> io::Result<()> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let mut fields = Map::new();
+    fields.insert(
+        "timestamp".to_string(),
+        Number::from_f64(timestamp.as_secs_f64()).into(),
+    );
+    fields.insert("level".to_string(), record.level().as_str().into());
+    fields.insert("target".to_string(), record.target().into());
+    fields.insert("message".to_string(), record.args().to_string().into());
+    // A field that fails to convert is left out rather than losing the line.
+    let _ = record.key_values().visit(&mut Fields(&mut fields));
+
+    serde_json::to_writer(&mut *buf, &fields)?;
+    writeln!(buf)
+}
+
+struct Fields<'a>(&'a mut Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let mut field = Field(serde_json::Value::Null);
+        value.visit(&mut field)?;
+        self.0.insert(key.to_string(), field.0);
+        Ok(())
+    }
+}
+
+// Keeps numbers and booleans as they are, and writes `None` as `null`.
+struct Field(serde_json::Value);
+
+impl<'v> VisitValue<'v> for Field {
+    fn visit_any(&mut self, value: Value) -> Result<(), kv::Error> {
+        self.0 = value.to_string().into();
+        Ok(())
+    }
+
+    fn visit_null(&mut self) -> Result<(), kv::Error> {
+        self.0 = serde_json::Value::Null;
+        Ok(())
+    }
+
+    fn visit_u64(&mut self, value: u64) -> Result<(), kv::Error> {
+        self.0 = value.into();
+        Ok(())
+    }
+
+    fn visit_i64(&mut self, value: i64) -> Result<(), kv::Error> {
+        self.0 = value.into();
+        Ok(())
+    }
+
+    fn visit_f64(&mut self, value: f64) -> Result<(), kv::Error> {
+        self.0 = Number::from_f64(value).into();
+        Ok(())
+    }
+
+    fn visit_bool(&mut self, value: bool) -> Result<(), kv::Error> {
+        self.0 = value.into();
+        Ok(())
+    }
+
+    fn visit_str(&mut self, value: &str) -> Result<(), kv::Error> {
+        self.0 = value.into();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_keys::ApiKeyStore;
+    use crate::client_ip::{ClientIpResolver, ForwardedHeader};
+    use crate::errors::ApiError;
+    use crate::metrics::Metrics;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{middleware::from_fn, App, HttpResponse};
+    use log::kv::ToValue;
+    use log::{Level, LevelFilter, Log, Metadata};
+    use parking_lot::Mutex;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    // Every line logged by any test, as `format_json` writes it.
+    static LINES: Mutex<Vec<serde_json::Value>> = Mutex::new(Vec::new());
+
+    struct Capture;
+
+    impl Log for Capture {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            let mut buf = Vec::new();
+            format_json(&mut buf, record).unwrap();
+            LINES.lock().push(serde_json::from_slice(&buf).unwrap());
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn logged_line(request_id: &str) -> Option<serde_json::Value> {
+        LINES
+            .lock()
+            .iter()
+            .find(|line| line["request_id"] == request_id)
+            .cloned()
+    }
+
+    #[actix_web::test]
+    async fn test_problem_instance_is_the_request_id() {
+        // Given
+        let _ = log::set_logger(&Capture);
+        log::set_max_level(LevelFilter::Info);
+        let limits = Limits::new(
+            ClientIpResolver::new(Vec::new(), ForwardedHeader::default(), None),
+            ApiKeyStore::empty(),
+            Vec::new(),
+            Vec::new(),
+            Arc::new(Metrics::new()),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(limits))
+                .wrap(from_fn(log_requests))
+                .route(
+                    "/missing",
+                    web::get().to(|req: HttpRequest| async move {
+                        Err::<HttpResponse, _>(ApiError::not_found("Nothing here.", instance(&req)))
+                    }),
+                ),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri("/missing")
+            .peer_addr(SocketAddr::new("203.0.113.9".parse().unwrap(), 40000))
+            .to_request();
+
+        // When
+        let res = test::call_service(&app, req).await;
+
+        // Then
+        let id = res.headers().get(REQUEST_ID).unwrap().to_str().unwrap();
+        let id = Uuid::parse_str(id).unwrap();
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["instance"], format!("urn:uuid:{id}"));
+        let line = logged_line(&id.to_string()).unwrap();
+        assert_eq!(line["client_ip"], "203.0.113.9");
+        assert_eq!(line["status"], 404);
+        assert!(line["candidates"].is_null());
+    }
+
+    #[test]
+    fn test_key_values_become_json_fields() {
+        // Given
+        let id = Uuid::nil();
+        let kvs: [(&str, Value); 7] = [
+            ("status", Value::from(429u16)),
+            ("compute_ms", Value::from(1.5)),
+            ("path", Value::from("/possible-words")),
+            ("candidates", None::<usize>.to_value()),
+            ("offset", Value::from(-3i64)),
+            ("partial", Value::from(true)),
+            ("request_id", Value::from_display(&id)),
+        ];
+        let mut buf = Vec::new();
+
+        // When
+        format_json(
+            &mut buf,
+            &Record::builder()
+                .level(Level::Info)
+                .target("wordle_solver")
+                .args(format_args!("POST /possible-words 429"))
+                .key_values(&kvs)
+                .build(),
+        )
+        .unwrap();
+
+        // Then
+        assert!(buf.ends_with(b"\n"));
+        let line: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["message"], "POST /possible-words 429");
+        assert_eq!(line["status"], 429);
+        assert_eq!(line["compute_ms"], 1.5);
+        assert_eq!(line["path"], "/possible-words");
+        assert!(line["candidates"].is_null());
+        assert_eq!(line["offset"], -3);
+        assert_eq!(line["partial"], true);
+        assert_eq!(line["request_id"], id.to_string());
+    }
+}
@@ -1,155 +1,220 @@
-mod entropy;
+mod api_keys;
+mod cache;
+mod client_ip;
+mod compute;
+mod config;
 mod errors;
-mod filters;
-mod models;
+mod health;
+mod limits;
+mod live;
+mod logging;
+mod metrics;
+mod negotiation;
+mod openapi;
 mod rate_limit;
+mod sessions;
 mod state;
 
 use actix_cors::Cors;
-use actix_web::middleware::{Compress, Logger};
-use core::f32;
-use entropy::calculate_entropy_for_words;
-use errors::ApiError;
-use filters::filter_words_by_guesses;
-use models::GuessBody;
+use actix_web::middleware::{from_fn, Compress};
+use api_keys::ApiKeyStore;
+use cache::ResponseCache;
+use client_ip::ClientIpResolver;
+use compute::ComputePool;
+use config::Config;
+use errors::{ApiError, ProblemDetails};
+use limits::{Limits, RateLimit};
+use metrics::Metrics;
 use rate_limit::IpRateLimiter;
+use sessions::SessionStore;
 use state::AppState;
-use std::{
-    fs::File,
-    io::{self, BufRead, BufReader},
-    net::{IpAddr, Ipv4Addr},
-};
+use std::time::Instant;
+use std::{io, sync::Arc, thread};
 
 use actix_web::{post, web, App, HttpResponse, HttpServer, ResponseError};
 use log::info;
-use std::env;
 
-use crate::models::{PossibleWords, Word};
-
-const ALLOWED_GUESSES_FILENAME: &str = "wordle-nyt-allowed-guesses.txt";
-const ANSWERS_FILENAME: &str = "wordle-nyt-answers.txt";
-
-#[post("/possible-words")]
+use wordle_solver::dictionary::get_all_words_from_file;
+use wordle_solver::models::{GuessBody, PossibleWords, WordListQuery};
+use wordle_solver::Constraints;
+
+#[utoipa::path(
+    post,
+    path = "/possible-words",
+    tag = "solver",
+    params(WordListQuery),
+    request_body = GuessBody,
+    responses(
+        (status = 200, description = "Words matching the guesses, ranked by entropy", content(
+            (PossibleWords = "application/json"),
+            (PossibleWords = "application/x-ndjson"),
+            (PossibleWords = "application/msgpack"),
+            (PossibleWords = "application/cbor"),
+        )),
+        (status = 400, description = "Malformed guesses or query parameters", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or unknown API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Disabled API key or denied address", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "The response could not be encoded", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 503, description = "Still ranking the opening guesses, or too busy to rank more", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+#[post("")]
 async fn possible_words(
     state: web::Data<AppState>,
     guesses: web::Json<GuessBody>,
+    query: web::Query<WordListQuery>,
     req: actix_web::HttpRequest,
 ) -> Result<HttpResponse, ApiError> {
-    let client_ip = req
-        .peer_addr()
-        .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |addr| addr.ip());
-
-    if !state.rate_limiter.check(client_ip) {
-        return Err(ApiError::rate_limited(
-            "Rate limit exceeded. Maximum 1 request per second allowed.",
-            req.path(),
-        ));
-    }
-
-    if guesses.0 .0.is_empty() {
-        return Ok(HttpResponse::Ok().json(&state.empty_guess_cache));
-    }
-
-    let filtered_words = filter_words_by_guesses(&state.words, &guesses.0 .0);
-
-    if filtered_words.is_empty() {
-        let response = PossibleWords {
-            word_list: filtered_words,
-            number_of_words: 0,
-            total_number_of_words: state.words.len(),
-            lowest_entropy: 0.0,
-            highest_entropy: 0.0,
-        };
-        return Ok(HttpResponse::Ok().json(response));
-    }
-
-    let filtered_words_with_entropy = calculate_entropy_for_words(&filtered_words);
-
-    let response = PossibleWords {
-        word_list: filtered_words_with_entropy.clone(),
-        number_of_words: filtered_words_with_entropy.len(),
-        total_number_of_words: state.words.len(),
-        lowest_entropy: filtered_words_with_entropy
-            .iter()
-            .map(|w| w.entropy)
-            .fold(f32::INFINITY, f32::min),
-        highest_entropy: filtered_words_with_entropy
-            .iter()
-            .map(|w| w.entropy)
-            .fold(f32::NEG_INFINITY, f32::max),
-    };
-    Ok(HttpResponse::Ok().json(response))
+    let constraints = Constraints::from_guesses(&guesses.0 .0);
+    let candidates = state.word_index.filter(&constraints);
+    let possible_words = state
+        .possible_words_for(&req, &constraints, &candidates, |cost| {
+            limits::charge(&req, cost)
+        })
+        .await?;
+    negotiation::respond(&req, possible_words, &query)
 }
 
 #[actix_web::main]
 async fn main() -> io::Result<()> {
-    env::set_var("RUST_LOG", "actix_web=info,wordle_solver=info");
-    env_logger::init();
+    let config = Config::load()?;
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(&config.log.level))
+        .format(logging::format_json)
+        .init();
 
-    let words = get_all_words_from_file()?;
-    let all_words_entropy = calculate_entropy_for_words(&words);
+    let words = get_all_words_from_file(&config.words.answers, &config.words.allowed_guesses)?;
 
-    let all_words_response = PossibleWords {
-        word_list: all_words_entropy.clone(),
-        number_of_words: words.len(),
-        total_number_of_words: words.len(),
-        lowest_entropy: all_words_entropy
-            .iter()
-            .map(|w| w.entropy)
-            .fold(f32::INFINITY, f32::min),
-        highest_entropy: all_words_entropy
-            .iter()
-            .map(|w| w.entropy)
-            .fold(f32::NEG_INFINITY, f32::max),
+    let api_keys = match &config.api_keys.file {
+        Some(path) => ApiKeyStore::from_file(path, config.api_keys.required)?,
+        None => ApiKeyStore::empty(),
     };
 
-    // One request per IP per second
+    let metrics = Arc::new(Metrics::new());
     let app_state = web::Data::new(AppState::new(
         words,
-        all_words_response,
-        IpRateLimiter::new(1, 1.0),
+        ResponseCache::new(config.cache.capacity),
+        SessionStore::new(config.sessions.ttl(), config.sessions.max_sessions),
+        Arc::clone(&metrics),
+        ComputePool::new(config.compute.threads, config.compute.queue_size)?,
+        config.compute.budget(),
     ));
 
-    info!("Starting HTTP Server on 5307");
-    HttpServer::new(move || {
-        let cors = Cors::default()
-            .allowed_origin("https://wordlesolver.umbra.mom")
+    // Rank the opening guesses while the server starts, rather than before;
+    // `/readyz` holds off traffic until they are done.
+    let warming = app_state.clone();
+    thread::spawn(move || {
+        let started = Instant::now();
+        warming.warm_up();
+        info!("Ranked the opening guesses in {:?}", started.elapsed());
+    });
+
+    let rate_limit = config.rate_limit;
+    let limits = Arc::new(Limits::new(
+        ClientIpResolver::new(
+            config.server.trusted_proxies.clone(),
+            config.server.forwarded_header,
+            rate_limit.ipv6_prefix_length,
+        ),
+        api_keys,
+        rate_limit.allow.clone(),
+        rate_limit.deny.clone(),
+        Arc::clone(&metrics),
+    ));
+    let policy = |scope, route| {
+        let (max_requests, window_seconds) = rate_limit.limit_for(route);
+        let limiter =
+            IpRateLimiter::new(max_requests, window_seconds, rate_limit.max_tracked_clients);
+        RateLimit::new(scope, Arc::clone(&limits), limiter)
+    };
+    let (max_messages, window_seconds) = rate_limit.limit_for(&rate_limit.live);
+    let live_allowance = web::Data::new(live::MessageAllowance {
+        max_messages,
+        window_seconds,
+    });
+    let suggestions_limit = policy("suggestions", &rate_limit.suggestions);
+    let sessions_limit = policy("sessions", &rate_limit.sessions);
+    let admin_limit = policy("admin", &rate_limit.admin);
+    let metrics = web::Data::from(metrics);
+    let limits = web::Data::from(limits);
+
+    let server_config = config.server;
+    let cors_origins = server_config.cors_origins;
+    info!(
+        "Starting HTTP Server on {}:{}",
+        server_config.bind_address, server_config.port
+    );
+    let mut server = HttpServer::new(move || {
+        let cors = cors_origins
+            .iter()
+            .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
             .allowed_methods(vec!["GET", "POST", "OPTIONS"])
             .allow_any_header()
+            .expose_headers(vec![
+                "RateLimit-Limit",
+                "RateLimit-Remaining",
+                "RateLimit-Reset",
+                "Retry-After",
+                "X-Request-Id",
+            ])
             .max_age(3600);
 
-        let json_cfg = web::JsonConfig::default().error_handler(|err, _req| {
-            let api_error = ApiError::bad_request(err.to_string(), _req.path());
+        let json_cfg = web::JsonConfig::default().error_handler(|err, req| {
+            let api_error = ApiError::bad_request(err.to_string(), logging::instance(req));
+            actix_web::error::InternalError::from_response(err, api_error.error_response()).into()
+        });
+
+        let query_cfg = web::QueryConfig::default().error_handler(|err, req| {
+            let api_error = ApiError::bad_request(err.to_string(), logging::instance(req));
             actix_web::error::InternalError::from_response(err, api_error.error_response()).into()
         });
 
         App::new()
             .app_data(app_state.clone())
-            .wrap(Logger::default())
+            .app_data(metrics.clone())
+            .app_data(limits.clone())
+            .wrap(from_fn(logging::log_requests))
             .wrap(cors)
             .wrap(Compress::default())
+            .wrap(from_fn(metrics::record_requests))
             .app_data(json_cfg)
-            .service(possible_words)
-    })
-    .bind(("0.0.0.0", 5307))?
-    .run()
-    .await
-}
-
-fn get_all_words_from_file() -> io::Result<Vec<Word>> {
-    fn read_words_from_file(filename: &str, is_answer: bool) -> io::Result<Vec<Word>> {
-        let file = File::open(filename)?;
-        let r = BufReader::new(file);
-        let words: Vec<Word> = r
-            .lines()
-            .filter_map(|line_result| line_result.ok().map(|word| Word::new(word, is_answer)))
-            .collect();
-        Ok(words)
+            .app_data(query_cfg)
+            .service(
+                web::scope("/possible-words")
+                    .wrap(suggestions_limit.clone())
+                    .service(possible_words),
+            )
+            .service(
+                web::scope("/live")
+                    .wrap(suggestions_limit.clone())
+                    .app_data(live_allowance.clone())
+                    .service(live::live_solver),
+            )
+            .service(
+                web::scope("/sessions")
+                    .wrap(sessions_limit.clone())
+                    .service(sessions::create_session)
+                    .service(sessions::add_guess)
+                    .service(sessions::get_session)
+                    .service(sessions::delete_session),
+            )
+            .service(
+                web::scope("/metrics")
+                    .wrap(admin_limit.clone())
+                    .service(metrics::prometheus_metrics),
+            )
+            .service(health::healthz)
+            .service(health::readyz)
+            .service(openapi::openapi_json)
+    });
+    if let Some(workers) = server_config.workers {
+        server = server.workers(workers.get());
     }
-
-    let mut words = read_words_from_file(ANSWERS_FILENAME, true)?;
-    let allowed_guesses = read_words_from_file(ALLOWED_GUESSES_FILENAME, false)?;
-
-    words.extend(allowed_guesses);
-    Ok(words)
+    server
+        // Drop a request as soon as its client hangs up, which cancels any
+        // ranking it was waiting for.
+        .h1_allow_half_closed(false)
+        .bind((server_config.bind_address, server_config.port))?
+        .run()
+        .await
 }
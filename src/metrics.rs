@@ -0,0 +1,359 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{get, web, Error, HttpResponse};
+use prometheus::core::Collector;
+use prometheus::proto::MetricFamily;
+use prometheus::{
+    exponential_buckets, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec,
+    IntGauge, IntGaugeVec, Opts, Registry, TextEncoder, TEXT_FORMAT,
+};
+use std::time::{Duration, Instant};
+
+use crate::limits::Limits;
+use crate::state::AppState;
+
+/// Counters and histograms updated as requests are served. Figures that other
+/// parts of the server already keep, such as cache hits, are read when
+/// scraped instead.
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    rate_limited: IntCounterVec,
+    candidates: Histogram,
+    ranking_duration: Histogram,
+    cut_short: IntCounterVec,
+    coalesced: IntCounter,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let requests = IntCounterVec::new(
+            Opts::new("wordle_solver_http_requests_total", "Requests served"),
+            &["route", "method", "status"],
+        )
+        .unwrap();
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "wordle_solver_http_request_duration_seconds",
+                "Time until the response headers were ready",
+            ),
+            &["route", "method", "status"],
+        )
+        .unwrap();
+        let rate_limited = IntCounterVec::new(
+            Opts::new(
+                "wordle_solver_rate_limit_rejections_total",
+                "Requests turned away for going over a rate limit or quota",
+            ),
+            &["scope"],
+        )
+        .unwrap();
+        let candidates = Histogram::with_opts(
+            HistogramOpts::new(
+                "wordle_solver_ranked_candidates",
+                "Words left to rank when a response was not cached",
+            )
+            .buckets(exponential_buckets(1.0, 4.0, 8).unwrap()),
+        )
+        .unwrap();
+        let ranking_duration = Histogram::with_opts(
+            HistogramOpts::new(
+                "wordle_solver_entropy_duration_seconds",
+                "Time spent ranking candidates by entropy",
+            )
+            .buckets(exponential_buckets(0.0001, 4.0, 10).unwrap()),
+        )
+        .unwrap();
+
+        let cut_short = IntCounterVec::new(
+            Opts::new(
+                "wordle_solver_rankings_cut_short_total",
+                "Rankings that went over budget and were estimated, or were cancelled",
+            ),
+            &["reason"],
+        )
+        .unwrap();
+
+        let coalesced = IntCounter::new(
+            "wordle_solver_coalesced_rankings_total",
+            "Requests that waited for a ranking another request had started",
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry
+            .register(Box::new(request_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(rate_limited.clone())).unwrap();
+        registry.register(Box::new(candidates.clone())).unwrap();
+        registry
+            .register(Box::new(ranking_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(cut_short.clone())).unwrap();
+        registry.register(Box::new(coalesced.clone())).unwrap();
+
+        Self {
+            registry,
+            requests,
+            request_duration,
+            rate_limited,
+            candidates,
+            ranking_duration,
+            cut_short,
+            coalesced,
+        }
+    }
+
+    pub fn observe_request(&self, route: &str, method: &str, status: u16, elapsed: Duration) {
+        let status = status.to_string();
+        let labels = [route, method, &status];
+        self.requests.with_label_values(&labels).inc();
+        self.request_duration
+            .with_label_values(&labels)
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_rate_limited(&self, scope: &str) {
+        self.rate_limited.with_label_values(&[scope]).inc();
+    }
+
+    pub fn observe_ranking(&self, candidates: usize, elapsed: Duration) {
+        self.candidates.observe(candidates as f64);
+        self.ranking_duration.observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_cut_short(&self, reason: &str) {
+        self.cut_short.with_label_values(&[reason]).inc();
+    }
+
+    pub fn observe_coalesced(&self) {
+        self.coalesced.inc();
+    }
+
+    #[cfg(test)]
+    pub fn coalesced(&self) -> u64 {
+        self.coalesced.get()
+    }
+
+    /// Everything in the Prometheus text format.
+    pub fn render(&self, state: &AppState, limits: &Limits) -> String {
+        let mut families = self.registry.gather();
+
+        let cache = &state.response_cache;
+        families.extend(counter(
+            "wordle_solver_response_cache_hits_total",
+            "Lookups answered from the response cache",
+            cache.hits(),
+        ));
+        families.extend(counter(
+            "wordle_solver_response_cache_misses_total",
+            "Lookups that had to rank the candidates",
+            cache.misses(),
+        ));
+        families.extend(gauge(
+            "wordle_solver_response_cache_entries",
+            "Responses held in the cache",
+            cache.len(),
+        ));
+        families.extend(gauge(
+            "wordle_solver_sessions",
+            "Sessions held, including expired ones not yet dropped",
+            state.sessions.len(),
+        ));
+        families.extend(gauge(
+            "wordle_solver_compute_jobs",
+            "Rankings running or waiting for a compute thread",
+            state.compute.jobs(),
+        ));
+
+        let by_scope = |name, help| IntCounterVec::new(Opts::new(name, help), &["scope"]).unwrap();
+        let idle_evictions = by_scope(
+            "wordle_solver_rate_limit_idle_evictions_total",
+            "Buckets forgotten after their client went quiet",
+        );
+        let capacity_evictions = by_scope(
+            "wordle_solver_rate_limit_capacity_evictions_total",
+            "Buckets forgotten to make room for a new client",
+        );
+        let tracked_clients = IntGaugeVec::new(
+            Opts::new(
+                "wordle_solver_rate_limit_tracked_clients",
+                "Clients with a rate limit bucket",
+            ),
+            &["scope"],
+        )
+        .unwrap();
+        for (scope, limiter) in limits.policies() {
+            idle_evictions
+                .with_label_values(&[scope])
+                .inc_by(limiter.idle_evictions());
+            capacity_evictions
+                .with_label_values(&[scope])
+                .inc_by(limiter.capacity_evictions());
+            tracked_clients
+                .with_label_values(&[scope])
+                .set(limiter.tracked_clients() as i64);
+        }
+        families.extend(idle_evictions.collect());
+        families.extend(capacity_evictions.collect());
+        families.extend(tracked_clients.collect());
+
+        // The encoder rejects families without samples, as the per scope ones
+        // are before any policy exists.
+        families.retain(|family| !family.get_metric().is_empty());
+        TextEncoder::new()
+            .encode_to_string(&families)
+            .unwrap_or_default()
+    }
+}
+
+fn counter(name: &str, help: &str, value: u64) -> Vec<MetricFamily> {
+    let counter = IntCounter::new(name, help).unwrap();
+    counter.inc_by(value);
+    counter.collect()
+}
+
+fn gauge(name: &str, help: &str, value: usize) -> Vec<MetricFamily> {
+    let gauge = IntGauge::new(name, help).unwrap();
+    gauge.set(value as i64);
+    gauge.collect()
+}
+
+/// Counts every request and how long it took, by the route it matched.
+pub async fn record_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let started = Instant::now();
+    let metrics = req.app_data::<web::Data<Metrics>>().cloned();
+    let res = next.call(req).await?;
+
+    if let Some(metrics) = metrics {
+        // Unmatched paths share a label, so scanners can't add a series each.
+        let route = res
+            .request()
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_string());
+        metrics.observe_request(
+            &route,
+            method_label(res.request().method()),
+            res.status().as_u16(),
+            started.elapsed(),
+        );
+    }
+    Ok(res)
+}
+
+// Made up methods share a label too.
+fn method_label(method: &Method) -> &'static str {
+    const STANDARD: [Method; 9] = [
+        Method::GET,
+        Method::HEAD,
+        Method::POST,
+        Method::PUT,
+        Method::DELETE,
+        Method::CONNECT,
+        Method::OPTIONS,
+        Method::TRACE,
+        Method::PATCH,
+    ];
+    STANDARD
+        .iter()
+        .find(|standard| *standard == method)
+        .map_or("other", Method::as_str)
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "health",
+    responses(
+        (status = 200, description = "Metrics in the Prometheus text format", content_type = "text/plain"),
+        (status = 403, description = "Denied address", body = crate::errors::ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = crate::errors::ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+#[get("")]
+pub async fn prometheus_metrics(
+    metrics: web::Data<Metrics>,
+    state: web::Data<AppState>,
+    limits: web::Data<Limits>,
+) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(TEXT_FORMAT)
+        .body(metrics.render(&state, &limits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_keys::ApiKeyStore;
+    use crate::client_ip::{ClientIpResolver, ForwardedHeader};
+    use crate::state::test_state;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::App;
+    use std::sync::Arc;
+
+    #[actix_web::test]
+    async fn test_requests_are_counted_by_route_pattern() {
+        // Given
+        let state = test_state(&["crane"], usize::MAX);
+        let metrics = Arc::clone(&state.metrics);
+        let limits = Limits::new(
+            ClientIpResolver::new(Vec::new(), ForwardedHeader::default(), None),
+            ApiKeyStore::empty(),
+            Vec::new(),
+            Vec::new(),
+            Arc::clone(&metrics),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(Arc::clone(&metrics)))
+                .app_data(web::Data::new(state))
+                .app_data(web::Data::new(limits))
+                .wrap(from_fn(record_requests))
+                .route("/items/{id}", web::get().to(HttpResponse::Ok))
+                .service(web::scope("/metrics").service(prometheus_metrics)),
+        )
+        .await;
+
+        // When
+        for uri in ["/items/1", "/items/2", "/scanner/probe"] {
+            test::call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+        }
+        let purge = Method::from_bytes(b"PURGE").unwrap();
+        test::call_service(
+            &app,
+            TestRequest::default()
+                .method(purge)
+                .uri("/scanner/probe")
+                .to_request(),
+        )
+        .await;
+        metrics.observe_rate_limited("suggestions");
+        let scraped =
+            test::call_service(&app, TestRequest::get().uri("/metrics").to_request()).await;
+        let text = String::from_utf8(test::read_body(scraped).await.to_vec()).unwrap();
+
+        // Then
+        assert!(text.contains(
+            r#"wordle_solver_http_requests_total{method="GET",route="/items/{id}",status="200"} 2"#
+        ));
+        assert!(text.contains(
+            r#"wordle_solver_http_requests_total{method="GET",route="unmatched",status="404"} 1"#
+        ));
+        assert!(text.contains(
+            r#"wordle_solver_http_requests_total{method="other",route="unmatched",status="404"} 1"#
+        ));
+        assert!(!text.contains("PURGE"));
+        assert!(
+            text.contains(r#"wordle_solver_rate_limit_rejections_total{scope="suggestions"} 1"#)
+        );
+        assert!(text.contains("wordle_solver_response_cache_hits_total 0"));
+    }
+}
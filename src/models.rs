@@ -1,16 +1,217 @@
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
+#[cfg(feature = "server")]
+use utoipa::{IntoParams, ToSchema};
 
 #[derive(Serialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
 pub struct PossibleWords {
+    /// Matching words ranked by descending entropy.
     pub word_list: Vec<Word>,
+    /// Number of words matching the guesses, before `limit` and `offset`.
     pub number_of_words: usize,
+    /// Number of words in the dictionary.
     pub total_number_of_words: usize,
     pub lowest_entropy: f32,
     pub highest_entropy: f32,
+    /// Ranking every word exactly would have taken too long, so some
+    /// entropies are estimated from a sample of the candidates.
+    pub partial: bool,
+}
+
+impl PossibleWords {
+    pub fn new(word_list: Vec<Word>, total_number_of_words: usize) -> Self {
+        let (lowest_entropy, highest_entropy) = if word_list.is_empty() {
+            (0.0, 0.0)
+        } else {
+            (
+                word_list
+                    .iter()
+                    .map(|w| w.entropy)
+                    .fold(f32::INFINITY, f32::min),
+                word_list
+                    .iter()
+                    .map(|w| w.entropy)
+                    .fold(f32::NEG_INFINITY, f32::max),
+            )
+        };
+        PossibleWords {
+            number_of_words: word_list.len(),
+            total_number_of_words,
+            lowest_entropy,
+            highest_entropy,
+            word_list,
+            partial: false,
+        }
+    }
+
+    pub fn select(&self, query: &WordListQuery) -> WordListPage<'_> {
+        let selection = self.selection(query);
+        WordListPage {
+            word_list: selection
+                .indices
+                .iter()
+                .map(|&i| WordView {
+                    word: &self.word_list[i],
+                    fields: selection.fields,
+                })
+                .collect(),
+            summary: selection.summary,
+        }
+    }
+
+    pub fn selection(&self, query: &WordListQuery) -> Selection {
+        let matching: Vec<usize> = self
+            .word_list
+            .iter()
+            .enumerate()
+            .filter(|(_, w)| !query.answers_only || w.is_answer)
+            .map(|(i, _)| i)
+            .collect();
+
+        // word_list is sorted by descending entropy, so the bounds of any
+        // subset are its first and last entries.
+        let (lowest_entropy, highest_entropy) = if query.answers_only {
+            match (matching.last(), matching.first()) {
+                (Some(&lowest), Some(&highest)) => (
+                    self.word_list[lowest].entropy,
+                    self.word_list[highest].entropy,
+                ),
+                _ => (0.0, 0.0),
+            }
+        } else {
+            (self.lowest_entropy, self.highest_entropy)
+        };
+
+        let summary = WordListSummary {
+            number_of_words: matching.len(),
+            total_number_of_words: self.total_number_of_words,
+            lowest_entropy,
+            highest_entropy,
+            partial: self.partial,
+        };
+        let indices = matching
+            .into_iter()
+            .skip(query.offset)
+            .take(query.limit.unwrap_or(usize::MAX))
+            .collect();
+
+        Selection {
+            indices,
+            fields: query.fields.unwrap_or_default(),
+            summary,
+        }
+    }
+}
+
+/// The ranked positions in `PossibleWords::word_list` picked by a
+/// `WordListQuery`, along with the counts and bounds of the words it matched.
+pub struct Selection {
+    pub indices: Vec<usize>,
+    pub fields: WordFields,
+    pub summary: WordListSummary,
+}
+
+#[derive(Deserialize, Default)]
+#[cfg_attr(feature = "server", derive(IntoParams))]
+#[cfg_attr(feature = "server", into_params(parameter_in = Query))]
+pub struct WordListQuery {
+    /// Maximum number of entries returned in `word_list`.
+    pub limit: Option<usize>,
+    /// Number of ranked entries to skip before `word_list` starts.
+    #[serde(default)]
+    pub offset: usize,
+    /// Only include possible answers.
+    #[serde(default)]
+    pub answers_only: bool,
+    /// Comma separated subset of `word`, `entropy` and `is_answer` to include.
+    #[cfg_attr(feature = "server", param(value_type = Option<String>, example = "word,entropy"))]
+    pub fields: Option<WordFields>,
+}
+
+#[derive(Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(try_from = "String")]
+pub struct WordFields {
+    pub word: bool,
+    pub entropy: bool,
+    pub is_answer: bool,
+}
+
+impl Default for WordFields {
+    fn default() -> Self {
+        WordFields {
+            word: true,
+            entropy: true,
+            is_answer: true,
+        }
+    }
+}
+
+impl TryFrom<String> for WordFields {
+    type Error = String;
+
+    fn try_from(fields: String) -> Result<Self, Self::Error> {
+        let mut selected = WordFields {
+            word: false,
+            entropy: false,
+            is_answer: false,
+        };
+        for field in fields.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            match field {
+                "word" => selected.word = true,
+                "entropy" => selected.entropy = true,
+                "is_answer" => selected.is_answer = true,
+                other => return Err(format!("Unknown field '{other}'.")),
+            }
+        }
+        if !(selected.word || selected.entropy || selected.is_answer) {
+            return Err("At least one field must be selected.".to_string());
+        }
+        Ok(selected)
+    }
+}
+
+#[derive(Serialize)]
+pub struct WordListPage<'a> {
+    pub word_list: Vec<WordView<'a>>,
+    #[serde(flatten)]
+    pub summary: WordListSummary,
+}
+
+#[derive(Serialize, Clone, Copy)]
+pub struct WordListSummary {
+    pub number_of_words: usize,
+    pub total_number_of_words: usize,
+    pub lowest_entropy: f32,
+    pub highest_entropy: f32,
+    pub partial: bool,
+}
+
+pub struct WordView<'a> {
+    pub word: &'a Word,
+    pub fields: WordFields,
+}
+
+impl Serialize for WordView<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        if self.fields.word {
+            map.serialize_entry("word", &self.word.word)?;
+        }
+        if self.fields.entropy {
+            map.serialize_entry("entropy", &self.word.entropy)?;
+        }
+        if self.fields.is_answer {
+            map.serialize_entry("is_answer", &self.word.is_answer)?;
+        }
+        map.end()
+    }
 }
 
 #[derive(Debug, PartialEq, PartialOrd, Clone, Serialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
 pub struct Word {
     pub word: String,
     pub entropy: f32,
@@ -20,6 +221,7 @@ pub struct Word {
 }
 
 impl Word {
+    /// Panics if `word` is shorter than five bytes; `try_new` checks it first.
     pub fn new(word: String, is_answer: bool) -> Self {
         let b = word.as_bytes();
         let bytes = [b[0], b[1], b[2], b[3], b[4]];
@@ -30,9 +232,18 @@ impl Word {
             bytes,
         }
     }
+
+    /// `word` in lower case, unless it is not five ASCII letters.
+    pub fn try_new(word: &str, is_answer: bool) -> Result<Self, String> {
+        if word.len() != 5 || !word.bytes().all(|b| b.is_ascii_alphabetic()) {
+            return Err(format!("'{word}' is not a word of 5 letters."));
+        }
+        Ok(Word::new(word.to_ascii_lowercase(), is_answer))
+    }
 }
 
-#[derive(Deserialize, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Debug)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
 pub enum Color {
     Grey,
     Yellow,
@@ -40,31 +251,124 @@ pub enum Color {
 }
 
 #[derive(Deserialize, Clone)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
 pub struct Guess {
+    /// Zero based row of the board the tile belongs to.
     pub turn: usize,
+    #[cfg_attr(feature = "server", schema(value_type = String, min_length = 1, max_length = 1, example = "c"))]
     pub letter: char,
+    /// Zero based column of the tile.
+    #[cfg_attr(feature = "server", schema(maximum = 4))]
     pub position: usize,
     pub color: Color,
 }
 
+impl Guess {
+    /// Checks the tile is a letter within the word, and lowercases it.
+    pub fn normalise(&mut self) -> Result<(), String> {
+        if !self.letter.is_ascii_alphabetic() {
+            return Err(format!("'{}' is not a letter.", self.letter));
+        }
+        if self.position >= 5 {
+            return Err("Guess positions must be between 0 and 4.".to_string());
+        }
+        self.letter = self.letter.to_ascii_lowercase();
+        Ok(())
+    }
+}
+
+/// Every tile of every guess made so far, five per turn.
 #[derive(Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
 #[serde(try_from = "Vec<Guess>")]
 pub struct GuessBody(pub Vec<Guess>);
 
 impl TryFrom<Vec<Guess>> for GuessBody {
     type Error = String;
 
-    fn try_from(guesses: Vec<Guess>) -> Result<Self, Self::Error> {
+    fn try_from(mut guesses: Vec<Guess>) -> Result<Self, Self::Error> {
         if !guesses.len().is_multiple_of(5) {
             return Err("All guesses must have 5 letters.".to_string());
         }
+        for guess in &mut guesses {
+            guess.normalise()?;
+        }
+        let mut rows: BTreeMap<usize, [bool; 5]> = BTreeMap::new();
+        for guess in &guesses {
+            let row = rows.entry(guess.turn).or_default();
+            if std::mem::replace(&mut row[guess.position], true) {
+                return Err(format!(
+                    "Turn {} has more than one tile at position {}.",
+                    guess.turn, guess.position
+                ));
+            }
+        }
+        if let Some(turn) = rows.iter().find(|(_, row)| row.contains(&false)) {
+            return Err(format!(
+                "Turn {} must have one tile at each position.",
+                turn.0
+            ));
+        }
         Ok(GuessBody(guesses))
     }
 }
 
+/// A guessed word and the colours it was given.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct Turn {
+    #[cfg_attr(feature = "server", schema(example = "crane"))]
+    pub word: String,
+    pub colors: [Color; 5],
+}
+
+impl Turn {
+    pub fn to_guesses(&self, turn: usize) -> Result<GuessBody, String> {
+        if self.word.chars().count() != 5 {
+            return Err("Guessed words must have 5 letters.".to_string());
+        }
+        let guesses: Vec<Guess> = self
+            .word
+            .chars()
+            .zip(self.colors)
+            .enumerate()
+            .map(|(position, (letter, color))| Guess {
+                turn,
+                letter,
+                position,
+                color,
+            })
+            .collect();
+        GuessBody::try_from(guesses)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Color, Guess, GuessBody};
+    use super::{Color, Guess, GuessBody, PossibleWords, Word, WordFields, WordListQuery};
+
+    fn construct_word(word: &str, entropy: f32, is_answer: bool) -> Word {
+        Word {
+            entropy,
+            ..Word::new(word.to_string(), is_answer)
+        }
+    }
+
+    fn construct_possible_words() -> PossibleWords {
+        PossibleWords {
+            word_list: vec![
+                construct_word("soare", 5.0, false),
+                construct_word("crane", 4.0, true),
+                construct_word("slate", 3.0, true),
+                construct_word("xylyl", 1.0, false),
+            ],
+            number_of_words: 4,
+            total_number_of_words: 4,
+            lowest_entropy: 1.0,
+            highest_entropy: 5.0,
+            partial: false,
+        }
+    }
 
     fn construct_guess(turn: usize, letter: char, position: usize, color: Color) -> Guess {
         Guess {
@@ -119,4 +423,161 @@ mod tests {
             Some("All guesses must have 5 letters.".to_string())
         );
     }
+
+    #[test]
+    fn guess_body_try_from_rejects_turns_without_one_tile_per_position() {
+        // Given
+        let repeated = vec![construct_guess(0, 'c', 0, Color::Green); 5];
+        let split = vec![
+            construct_guess(0, 'c', 0, Color::Green),
+            construct_guess(0, 'r', 1, Color::Grey),
+            construct_guess(0, 'a', 2, Color::Yellow),
+            construct_guess(1, 'n', 3, Color::Grey),
+            construct_guess(1, 'e', 4, Color::Grey),
+        ];
+
+        // When
+        let repeated_result = GuessBody::try_from(repeated);
+        let split_result = GuessBody::try_from(split);
+
+        // Then
+        assert_eq!(
+            repeated_result.err(),
+            Some("Turn 0 has more than one tile at position 0.".to_string())
+        );
+        assert_eq!(
+            split_result.err(),
+            Some("Turn 0 must have one tile at each position.".to_string())
+        );
+    }
+
+    #[test]
+    fn select_applies_offset_and_limit_after_ranking() {
+        // Given
+        let possible_words = construct_possible_words();
+        let query = WordListQuery {
+            limit: Some(2),
+            offset: 1,
+            ..WordListQuery::default()
+        };
+
+        // When
+        let page = possible_words.select(&query);
+
+        // Then
+        let words: Vec<&str> = page
+            .word_list
+            .iter()
+            .map(|w| w.word.word.as_str())
+            .collect();
+        assert_eq!(words, vec!["crane", "slate"]);
+        assert_eq!(page.summary.number_of_words, 4);
+        assert_eq!(page.summary.lowest_entropy, 1.0);
+        assert_eq!(page.summary.highest_entropy, 5.0);
+    }
+
+    #[test]
+    fn select_answers_only_recomputes_counts_and_bounds() {
+        // Given
+        let possible_words = construct_possible_words();
+        let query = WordListQuery {
+            answers_only: true,
+            ..WordListQuery::default()
+        };
+
+        // When
+        let page = possible_words.select(&query);
+
+        // Then
+        let words: Vec<&str> = page
+            .word_list
+            .iter()
+            .map(|w| w.word.word.as_str())
+            .collect();
+        assert_eq!(words, vec!["crane", "slate"]);
+        assert_eq!(page.summary.number_of_words, 2);
+        assert_eq!(page.summary.total_number_of_words, 4);
+        assert_eq!(page.summary.lowest_entropy, 3.0);
+        assert_eq!(page.summary.highest_entropy, 4.0);
+    }
+
+    #[test]
+    fn select_serialises_only_requested_fields() {
+        // Given
+        let possible_words = construct_possible_words();
+        let query = WordListQuery {
+            limit: Some(1),
+            fields: Some(WordFields::try_from("word".to_string()).unwrap()),
+            ..WordListQuery::default()
+        };
+
+        // When
+        let json = serde_json::to_value(possible_words.select(&query)).unwrap();
+
+        // Then
+        assert_eq!(json["word_list"], serde_json::json!([{ "word": "soare" }]));
+    }
+
+    #[test]
+    fn word_fields_try_from_rejects_unknown_and_empty_fields() {
+        // Given
+        let unknown = "word,score".to_string();
+        let empty = ",".to_string();
+
+        // When
+        let unknown_result = WordFields::try_from(unknown);
+        let empty_result = WordFields::try_from(empty);
+
+        // Then
+        assert_eq!(
+            unknown_result.err(),
+            Some("Unknown field 'score'.".to_string())
+        );
+        assert_eq!(
+            empty_result.err(),
+            Some("At least one field must be selected.".to_string())
+        );
+    }
+
+    #[test]
+    fn word_try_new_accepts_only_five_ascii_letters() {
+        // Given
+        let words = ["Crane", "cran", "", "cranés", "cr4ne"];
+
+        // When
+        let results = words.map(|word| Word::try_new(word, true).map(|word| word.word));
+
+        // Then
+        assert_eq!(
+            results,
+            [
+                Ok("crane".to_string()),
+                Err("'cran' is not a word of 5 letters.".to_string()),
+                Err("'' is not a word of 5 letters.".to_string()),
+                Err("'cranés' is not a word of 5 letters.".to_string()),
+                Err("'cr4ne' is not a word of 5 letters.".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn guess_body_try_from_rejects_invalid_letters_and_positions() {
+        // Given
+        let non_letter = vec![construct_guess(0, '1', 0, Color::Grey); 5];
+        let out_of_range = vec![construct_guess(0, 'c', 5, Color::Grey); 5];
+
+        // When
+        let non_letter_result = GuessBody::try_from(non_letter);
+        let out_of_range_result = GuessBody::try_from(out_of_range);
+
+        // Then
+        assert_eq!(
+            non_letter_result.err(),
+            Some("'1' is not a letter.".to_string())
+        );
+        assert_eq!(
+            out_of_range_result.err(),
+            Some("Guess positions must be between 0 and 4.".to_string())
+        );
+    }
 }
@@ -0,0 +1,307 @@
+use actix_web::http::header::{self, Accept, Header, Quality};
+use actix_web::web::Bytes;
+use actix_web::{HttpRequest, HttpResponse};
+use futures_util::stream;
+use std::sync::Arc;
+
+use crate::errors::ApiError;
+use crate::logging;
+use wordle_solver::models::{PossibleWords, WordListQuery, WordView};
+
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+const NDJSON_WORDS_PER_CHUNK: usize = 256;
+const MESSAGE_PACK_CONTENT_TYPE: &str = "application/msgpack";
+const CBOR_CONTENT_TYPE: &str = "application/cbor";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ResponseFormat {
+    Json,
+    NdJson,
+    MessagePack,
+    Cbor,
+}
+
+impl ResponseFormat {
+    pub fn negotiate(req: &HttpRequest) -> Self {
+        let Ok(Accept(ranges)) = Accept::parse(req) else {
+            return Self::Json;
+        };
+        // A quality of 0 means the client will not take that type at all.
+        let acceptable = ranges
+            .into_iter()
+            .filter(|range| range.quality > Quality::ZERO)
+            .collect();
+
+        Accept(acceptable)
+            .ranked()
+            .iter()
+            .find_map(|mime| match mime.essence_str() {
+                "application/json" | "application/*" | "*/*" => Some(Self::Json),
+                NDJSON_CONTENT_TYPE => Some(Self::NdJson),
+                MESSAGE_PACK_CONTENT_TYPE | "application/x-msgpack" | "application/vnd.msgpack" => {
+                    Some(Self::MessagePack)
+                }
+                CBOR_CONTENT_TYPE => Some(Self::Cbor),
+                _ => None,
+            })
+            .unwrap_or(Self::Json)
+    }
+}
+
+pub fn respond(
+    req: &HttpRequest,
+    possible_words: Arc<PossibleWords>,
+    query: &WordListQuery,
+) -> Result<HttpResponse, ApiError> {
+    let response = match ResponseFormat::negotiate(req) {
+        ResponseFormat::Json => HttpResponse::Ok()
+            .insert_header((header::VARY, "Accept"))
+            .json(possible_words.select(query)),
+        ResponseFormat::NdJson => ndjson_response(possible_words, query),
+        ResponseFormat::MessagePack => {
+            let body = rmp_serde::to_vec_named(&possible_words.select(query))
+                .map_err(|err| ApiError::internal(err.to_string(), logging::instance(req)))?;
+            binary_response(MESSAGE_PACK_CONTENT_TYPE, body)
+        }
+        ResponseFormat::Cbor => {
+            let mut body = Vec::new();
+            ciborium::into_writer(&possible_words.select(query), &mut body)
+                .map_err(|err| ApiError::internal(err.to_string(), logging::instance(req)))?;
+            binary_response(CBOR_CONTENT_TYPE, body)
+        }
+    };
+    Ok(response)
+}
+
+fn binary_response(content_type: &'static str, body: Vec<u8>) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, content_type))
+        .insert_header((header::VARY, "Accept"))
+        .body(body)
+}
+
+// One line per ranked word, followed by a summary line with the counts and
+// entropy bounds. Words are serialised lazily in chunks as the body is polled.
+fn ndjson_response(possible_words: Arc<PossibleWords>, query: &WordListQuery) -> HttpResponse {
+    let selection = possible_words.selection(query);
+    let summary = selection.summary;
+    let chunk_starts = (0..selection.indices.len()).step_by(NDJSON_WORDS_PER_CHUNK);
+
+    let words = stream::iter(chunk_starts.map(move |start| {
+        let end = (start + NDJSON_WORDS_PER_CHUNK).min(selection.indices.len());
+        let mut chunk = Vec::new();
+        for &i in &selection.indices[start..end] {
+            let view = WordView {
+                word: &possible_words.word_list[i],
+                fields: selection.fields,
+            };
+            serde_json::to_writer(&mut chunk, &view)?;
+            chunk.push(b'\n');
+        }
+        Ok::<_, actix_web::Error>(Bytes::from(chunk))
+    }));
+
+    let trailer = stream::once(async move {
+        let mut line = serde_json::to_vec(&summary)?;
+        line.push(b'\n');
+        Ok::<_, actix_web::Error>(Bytes::from(line))
+    });
+
+    HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, NDJSON_CONTENT_TYPE))
+        .insert_header((header::VARY, "Accept"))
+        .streaming(futures_util::StreamExt::chain(words, trailer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use wordle_solver::models::Word;
+
+    fn crane() -> Arc<PossibleWords> {
+        Arc::new(PossibleWords {
+            word_list: vec![Word::new("crane".to_string(), true)],
+            number_of_words: 1,
+            total_number_of_words: 1,
+            lowest_entropy: 0.0,
+            highest_entropy: 0.0,
+            partial: false,
+        })
+    }
+
+    #[test]
+    fn negotiate_prefers_ndjson_when_requested() {
+        // Given
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/x-ndjson"))
+            .to_http_request();
+
+        // When
+        let format = ResponseFormat::negotiate(&req);
+
+        // Then
+        assert_eq!(format, ResponseFormat::NdJson);
+    }
+
+    #[test]
+    fn negotiate_follows_quality_values() {
+        // Given
+        let preferred = TestRequest::default()
+            .insert_header((
+                header::ACCEPT,
+                "application/x-ndjson;q=0.5, application/json",
+            ))
+            .to_http_request();
+        let refused = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/x-ndjson;q=0, application/json"))
+            .to_http_request();
+        let only_refused = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/x-ndjson;q=0"))
+            .to_http_request();
+
+        // When & Then
+        assert_eq!(ResponseFormat::negotiate(&preferred), ResponseFormat::Json);
+        assert_eq!(ResponseFormat::negotiate(&refused), ResponseFormat::Json);
+        assert_eq!(
+            ResponseFormat::negotiate(&only_refused),
+            ResponseFormat::Json
+        );
+    }
+
+    #[test]
+    fn negotiate_falls_back_to_json() {
+        // Given
+        let missing = TestRequest::default().to_http_request();
+        let unsupported = TestRequest::default()
+            .insert_header((header::ACCEPT, "text/html"))
+            .to_http_request();
+
+        // When & Then
+        assert_eq!(ResponseFormat::negotiate(&missing), ResponseFormat::Json);
+        assert_eq!(
+            ResponseFormat::negotiate(&unsupported),
+            ResponseFormat::Json
+        );
+    }
+
+    #[test]
+    fn negotiate_recognises_binary_formats() {
+        // Given
+        let msgpack = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/x-msgpack"))
+            .to_http_request();
+        let cbor = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/cbor, application/json;q=0.9"))
+            .to_http_request();
+
+        // When & Then
+        assert_eq!(
+            ResponseFormat::negotiate(&msgpack),
+            ResponseFormat::MessagePack
+        );
+        assert_eq!(ResponseFormat::negotiate(&cbor), ResponseFormat::Cbor);
+    }
+
+    #[actix_web::test]
+    async fn respond_encodes_message_pack_with_named_fields() {
+        // Given
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/msgpack"))
+            .to_http_request();
+
+        // When
+        let response = respond(&req, crane(), &WordListQuery::default()).unwrap();
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+
+        // Then
+        let decoded: serde_json::Value = rmp_serde::from_slice(&body).unwrap();
+        assert_eq!(decoded["word_list"][0]["word"], "crane");
+        assert_eq!(decoded["number_of_words"], 1);
+    }
+
+    #[actix_web::test]
+    async fn respond_encodes_cbor() {
+        // Given
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, CBOR_CONTENT_TYPE))
+            .to_http_request();
+
+        // When
+        let response = respond(&req, crane(), &WordListQuery::default()).unwrap();
+        let content_type = response.headers().get(header::CONTENT_TYPE).cloned();
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+
+        // Then
+        assert_eq!(content_type.unwrap(), CBOR_CONTENT_TYPE);
+        let decoded: serde_json::Value = ciborium::from_reader(&body[..]).unwrap();
+        assert_eq!(decoded["word_list"][0]["word"], "crane");
+        assert_eq!(decoded["number_of_words"], 1);
+    }
+
+    #[actix_web::test]
+    async fn respond_defaults_to_json_that_varies_by_accept() {
+        // Given
+        let req = TestRequest::default().to_http_request();
+
+        // When
+        let response = respond(&req, crane(), &WordListQuery::default()).unwrap();
+        let headers = response.headers().clone();
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+
+        // Then
+        assert_eq!(
+            headers.get(header::CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+        assert_eq!(headers.get(header::VARY).unwrap(), "Accept");
+        let decoded: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(decoded["word_list"][0]["word"], "crane");
+    }
+
+    #[actix_web::test]
+    async fn respond_streams_one_word_per_line_then_the_summary() {
+        // Given
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, NDJSON_CONTENT_TYPE))
+            .to_http_request();
+        let words: Vec<Word> = (0..NDJSON_WORDS_PER_CHUNK + 44)
+            .map(|i| Word::new(format!("w{i:04}"), true))
+            .collect();
+        let possible_words = Arc::new(PossibleWords {
+            number_of_words: words.len(),
+            total_number_of_words: words.len(),
+            word_list: words,
+            lowest_entropy: 0.0,
+            highest_entropy: 0.0,
+            partial: true,
+        });
+
+        // When
+        let response = respond(&req, possible_words, &WordListQuery::default()).unwrap();
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+
+        // Then
+        let lines: Vec<serde_json::Value> = body
+            .split(|&byte| byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        let (summary, words) = lines.split_last().unwrap();
+        assert_eq!(words.len(), NDJSON_WORDS_PER_CHUNK + 44);
+        for (i, word) in words.iter().enumerate() {
+            assert_eq!(word["word"], format!("w{i:04}"));
+        }
+        assert_eq!(summary["number_of_words"], NDJSON_WORDS_PER_CHUNK + 44);
+        assert_eq!(summary["partial"], true);
+        assert!(body.ends_with(b"\n"));
+    }
+}
@@ -0,0 +1,107 @@
+use actix_web::{get, HttpResponse};
+use utoipa::OpenApi;
+
+use crate::errors::ProblemDetails;
+use crate::health::{self, Health, Readiness, ReadinessChecks};
+use crate::sessions::{self, SessionResponse};
+use wordle_solver::models::{Color, Guess, GuessBody, PossibleWords, Turn, Word};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        description = "Suggests Wordle guesses from the grey, yellow and green letters seen so far."
+    ),
+    paths(
+        crate::possible_words,
+        sessions::create_session,
+        sessions::add_guess,
+        sessions::get_session,
+        sessions::delete_session,
+        crate::live::live_solver,
+        health::healthz,
+        health::readyz,
+        crate::metrics::prometheus_metrics,
+        openapi_json
+    ),
+    components(schemas(
+        GuessBody,
+        Guess,
+        Color,
+        PossibleWords,
+        Word,
+        Turn,
+        SessionResponse,
+        Health,
+        Readiness,
+        ReadinessChecks,
+        ProblemDetails
+    ))
+)]
+pub struct ApiDoc;
+
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    responses((status = 200, description = "This OpenAPI document", content_type = "application/json"))
+)]
+#[get("/openapi.json")]
+pub async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::App;
+
+    #[actix_web::test]
+    async fn openapi_json_serves_the_document() {
+        // Given
+        let app = test::init_service(App::new().service(openapi_json)).await;
+
+        // When
+        let res =
+            test::call_service(&app, TestRequest::get().uri("/openapi.json").to_request()).await;
+
+        // Then
+        let doc: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(doc, serde_json::to_value(ApiDoc::openapi()).unwrap());
+    }
+
+    #[test]
+    fn api_doc_describes_every_route_and_model() {
+        // Given
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        // When
+        let paths = doc["paths"].as_object().unwrap();
+        let schemas = doc["components"]["schemas"].as_object().unwrap();
+
+        // Then
+        assert!(doc["openapi"].as_str().unwrap().starts_with("3.1"));
+        assert!(paths.contains_key("/possible-words"));
+        assert!(paths.contains_key("/sessions"));
+        assert!(paths.contains_key("/sessions/{id}"));
+        assert!(paths.contains_key("/sessions/{id}/guesses"));
+        assert!(paths.contains_key("/live"));
+        assert!(paths.contains_key("/healthz"));
+        assert!(paths.contains_key("/readyz"));
+        assert!(paths.contains_key("/metrics"));
+        assert!(paths.contains_key("/openapi.json"));
+        for schema in [
+            "GuessBody",
+            "Guess",
+            "Color",
+            "PossibleWords",
+            "Word",
+            "Turn",
+            "SessionResponse",
+            "Readiness",
+            "ProblemDetails",
+        ] {
+            assert!(schemas.contains_key(schema), "missing schema {schema}");
+        }
+        assert!(schemas["Word"]["properties"].get("bytes").is_none());
+    }
+}
@@ -1,48 +1,167 @@
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use log::debug;
+use lru::LruCache;
 use parking_lot::Mutex;
-use std::collections::HashMap;
+use std::hash::Hash;
 use std::net::IpAddr;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-pub struct IpRateLimiter {
-    buckets: Arc<Mutex<HashMap<IpAddr, TokenBucket>>>,
+const RATE_LIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+const RATE_LIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+const RATE_LIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+/// Token buckets per client, where a client is whatever `K` identifies, such
+/// as an address or an API key.
+pub struct RateLimiter<K> {
+    // Least recently seen clients first, so idle buckets can be dropped from
+    // the front and the map never holds more than `max_tracked` clients.
+    buckets: Arc<Mutex<LruCache<K, TokenBucket>>>,
     max_requests: u32,
     window_seconds: f64,
+    idle_evictions: AtomicU64,
+    capacity_evictions: AtomicU64,
+}
+
+/// The outcome of a rate limit check, kept on the request so that the
+/// response can report it.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Until the client's quota is full again.
+    pub reset: Duration,
+    /// Until the client can make another request, zero if it can now.
+    pub retry_after: Duration,
 }
 
+impl RateLimitDecision {
+    pub fn insert_headers(&self, headers: &mut HeaderMap) {
+        headers.insert(RATE_LIMIT_LIMIT, HeaderValue::from(self.limit));
+        headers.insert(RATE_LIMIT_REMAINING, HeaderValue::from(self.remaining));
+        headers.insert(
+            RATE_LIMIT_RESET,
+            HeaderValue::from(whole_seconds(self.reset)),
+        );
+    }
+}
+
+/// Rounded up, so that a client waiting this long will not be turned away.
+pub fn whole_seconds(duration: Duration) -> u64 {
+    duration.as_secs_f64().ceil() as u64
+}
+
+pub type IpRateLimiter = RateLimiter<IpAddr>;
+
 struct TokenBucket {
     tokens: f64,
-    last_refill: std::time::Instant,
+    last_refill: Instant,
 }
 
-impl IpRateLimiter {
-    pub fn new(max_requests: u32, window_seconds: f64) -> Self {
+impl<K: Hash + Eq> RateLimiter<K> {
+    pub fn new(max_requests: u32, window_seconds: f64, max_tracked: NonZeroUsize) -> Self {
         Self {
-            buckets: Arc::new(Mutex::new(HashMap::new())),
+            buckets: Arc::new(Mutex::new(LruCache::new(max_tracked))),
             max_requests,
             window_seconds,
+            idle_evictions: AtomicU64::new(0),
+            capacity_evictions: AtomicU64::new(0),
         }
     }
 
-    pub fn check(&self, ip: IpAddr) -> bool {
+    /// Admits a request costing a single token, with the tokens left and how
+    /// long until the next one.
+    pub fn check(&self, client: K) -> RateLimitDecision {
+        self.check_n(client, 1)
+    }
+
+    /// Admits a request costing `cost` tokens. One costing more than a full
+    /// bucket is admitted once the bucket is full and leaves it in debt, so
+    /// the client waits for the whole cost to refill before its next request.
+    pub fn check_n(&self, client: K, cost: u32) -> RateLimitDecision {
+        self.check_more(client, cost, 0)
+    }
+
+    /// Admits `cost` more tokens of work on a request that has already paid
+    /// `paid`, exactly when a single `check_n` of the two together would have.
+    pub fn check_more(&self, client: K, cost: u32, paid: u32) -> RateLimitDecision {
         let mut buckets = self.buckets.lock();
-        let now = std::time::Instant::now();
+        let now = Instant::now();
+        self.evict_idle(&mut buckets, now);
 
-        let bucket = buckets.entry(ip).or_insert_with(|| TokenBucket {
+        if !buckets.contains(&client) && buckets.len() == buckets.cap().get() {
+            self.capacity_evictions.fetch_add(1, Ordering::Relaxed);
+            debug!(
+                "Rate limiter is full; forgot the least recently seen client ({} idle, {} capacity evictions)",
+                self.idle_evictions(),
+                self.capacity_evictions()
+            );
+        }
+        let bucket = buckets.get_or_insert_mut(client, || TokenBucket {
             tokens: f64::from(self.max_requests),
             last_refill: now,
         });
 
-        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
-        let refill_rate = f64::from(self.max_requests) / self.window_seconds;
-        bucket.tokens = (bucket.tokens + elapsed * refill_rate).min(f64::from(self.max_requests));
+        bucket.tokens = self.refilled(bucket, now);
         bucket.last_refill = now;
+        let refill_rate = self.refill_rate();
 
         // Check if we have tokens available
-        if bucket.tokens >= 1.0 {
-            bucket.tokens -= 1.0;
-            true
-        } else {
-            false
+        let cost = f64::from(cost);
+        let required = cost.min(f64::from(self.max_requests.saturating_sub(paid)));
+        let allowed = bucket.tokens >= required;
+        if allowed {
+            bucket.tokens -= cost;
+        }
+
+        let seconds_until = |tokens: f64| {
+            Duration::from_secs_f64(((tokens - bucket.tokens) / refill_rate).max(0.0))
+        };
+        RateLimitDecision {
+            allowed,
+            limit: self.max_requests,
+            remaining: bucket.tokens.max(0.0) as u32,
+            reset: seconds_until(f64::from(self.max_requests)),
+            retry_after: seconds_until(1.0),
+        }
+    }
+
+    pub fn idle_evictions(&self) -> u64 {
+        self.idle_evictions.load(Ordering::Relaxed)
+    }
+
+    pub fn capacity_evictions(&self) -> u64 {
+        self.capacity_evictions.load(Ordering::Relaxed)
+    }
+
+    pub fn tracked_clients(&self) -> usize {
+        self.buckets.lock().len()
+    }
+
+    fn refill_rate(&self) -> f64 {
+        f64::from(self.max_requests) / self.window_seconds
+    }
+
+    // The tokens `bucket` holds by `now`, up to a full bucket.
+    fn refilled(&self, bucket: &TokenBucket, now: Instant) -> f64 {
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        (bucket.tokens + elapsed * self.refill_rate()).min(f64::from(self.max_requests))
+    }
+
+    // A bucket that has refilled completely is no different from the fresh one
+    // the client would get if it came back. One still in debt from a costly
+    // request is kept, or forgetting it would cancel the debt.
+    fn evict_idle(&self, buckets: &mut LruCache<K, TokenBucket>, now: Instant) {
+        let full = f64::from(self.max_requests);
+        while buckets
+            .peek_lru()
+            .is_some_and(|(_, bucket)| self.refilled(bucket, now) >= full)
+        {
+            buckets.pop_lru();
+            self.idle_evictions.fetch_add(1, Ordering::Relaxed);
         }
     }
 }
@@ -52,30 +171,35 @@ mod tests {
     use super::*;
     use std::str::FromStr;
 
+    const MAX_TRACKED: NonZeroUsize = NonZeroUsize::new(100).unwrap();
+
     #[test]
     fn test_rate_limiter_allows_requests_within_quota() {
         // Given
-        let limiter = IpRateLimiter::new(5, 1.0);
+        let limiter = IpRateLimiter::new(5, 1.0, MAX_TRACKED);
         let ip = IpAddr::from_str("127.0.0.1").unwrap();
 
         // When & Then
         for _ in 0..5 {
-            assert!(limiter.check(ip), "Request should be allowed within quota");
+            assert!(
+                limiter.check(ip).allowed,
+                "Request should be allowed within quota"
+            );
         }
     }
 
     #[test]
     fn test_rate_limiter_blocks_excess_requests() {
         // Given
-        let limiter = IpRateLimiter::new(2, 1.0);
+        let limiter = IpRateLimiter::new(2, 1.0, MAX_TRACKED);
         let ip = IpAddr::from_str("127.0.0.1").unwrap();
 
         // When & Then
-        assert!(limiter.check(ip));
-        assert!(limiter.check(ip));
+        assert!(limiter.check(ip).allowed);
+        assert!(limiter.check(ip).allowed);
 
         assert!(
-            !limiter.check(ip),
+            !limiter.check(ip).allowed,
             "Request should be blocked when quota exceeded"
         );
     }
@@ -83,14 +207,146 @@ mod tests {
     #[test]
     fn test_rate_limiter_per_ip() {
         // Given
-        let limiter = IpRateLimiter::new(1, 1.0);
+        let limiter = IpRateLimiter::new(1, 1.0, MAX_TRACKED);
         let ip1 = IpAddr::from_str("127.0.0.1").unwrap();
         let ip2 = IpAddr::from_str("127.0.0.2").unwrap();
 
         // When & Then
-        assert!(limiter.check(ip1));
-        assert!(limiter.check(ip2));
-        assert!(!limiter.check(ip1), "IP1 should be rate limited");
-        assert!(!limiter.check(ip2), "IP2 should be rate limited");
+        assert!(limiter.check(ip1).allowed);
+        assert!(limiter.check(ip2).allowed);
+        assert!(!limiter.check(ip1).allowed, "IP1 should be rate limited");
+        assert!(!limiter.check(ip2).allowed, "IP2 should be rate limited");
+    }
+
+    #[test]
+    fn test_rate_limiter_evicts_idle_buckets() {
+        // Given
+        let limiter = IpRateLimiter::new(1, 1e-9, MAX_TRACKED);
+        let ip1 = IpAddr::from_str("127.0.0.1").unwrap();
+        let ip2 = IpAddr::from_str("127.0.0.2").unwrap();
+
+        // When
+        limiter.check(ip1);
+        limiter.check(ip2);
+
+        // Then
+        assert_eq!(limiter.tracked_clients(), 1);
+        assert_eq!(limiter.idle_evictions(), 1);
+    }
+
+    #[test]
+    fn test_buckets_in_debt_are_not_forgotten_after_a_window() {
+        // Given
+        let limiter = IpRateLimiter::new(1, 0.05, MAX_TRACKED);
+        let ip1 = IpAddr::from_str("127.0.0.1").unwrap();
+        let ip2 = IpAddr::from_str("127.0.0.2").unwrap();
+        assert!(limiter.check_n(ip1, 5).allowed);
+
+        // When
+        std::thread::sleep(Duration::from_secs_f64(0.06));
+        limiter.check(ip2);
+        let next = limiter.check(ip1);
+
+        // Then
+        assert_eq!(limiter.idle_evictions(), 0);
+        assert!(!next.allowed, "The debt should outlive the window");
+    }
+
+    #[test]
+    fn test_rate_limiter_caps_tracked_clients() {
+        // Given
+        let limiter = IpRateLimiter::new(1, 60.0, NonZeroUsize::new(2).unwrap());
+        let ips: Vec<IpAddr> = ["10.0.0.1", "10.0.0.2", "10.0.0.3"]
+            .iter()
+            .map(|ip| IpAddr::from_str(ip).unwrap())
+            .collect();
+
+        // When
+        for &ip in &ips {
+            assert!(limiter.check(ip).allowed);
+        }
+
+        // Then
+        assert_eq!(limiter.tracked_clients(), 2);
+        assert_eq!(limiter.capacity_evictions(), 1);
+        assert!(
+            limiter.check(ips[0]).allowed,
+            "The least recently seen client should have been forgotten"
+        );
+        assert!(!limiter.check(ips[2]).allowed);
+    }
+
+    #[test]
+    fn test_rate_limiter_reports_remaining_quota_and_retry_delay() {
+        // Given
+        let limiter = IpRateLimiter::new(2, 10.0, MAX_TRACKED);
+        let ip = IpAddr::from_str("127.0.0.1").unwrap();
+
+        // When
+        let first = limiter.check(ip);
+        limiter.check(ip);
+        let blocked = limiter.check(ip);
+
+        // Then
+        assert_eq!((first.limit, first.remaining), (2, 1));
+        assert_eq!(whole_seconds(first.retry_after), 0);
+        assert_eq!(whole_seconds(first.reset), 5);
+        assert!(!blocked.allowed);
+        assert_eq!(blocked.remaining, 0);
+        assert_eq!(whole_seconds(blocked.retry_after), 5);
+        assert_eq!(whole_seconds(blocked.reset), 10);
+    }
+
+    #[test]
+    fn test_weighted_requests_consume_their_cost() {
+        // Given
+        let limiter = IpRateLimiter::new(10, 10.0, MAX_TRACKED);
+        let ip = IpAddr::from_str("127.0.0.1").unwrap();
+
+        // When
+        let heavy = limiter.check_n(ip, 8);
+        let too_heavy = limiter.check_n(ip, 3);
+        let light = limiter.check_n(ip, 2);
+
+        // Then
+        assert!(heavy.allowed);
+        assert_eq!(heavy.remaining, 2);
+        assert!(!too_heavy.allowed);
+        assert!(light.allowed);
+        assert_eq!(light.remaining, 0);
+    }
+
+    #[test]
+    fn test_requests_costlier_than_the_bucket_leave_it_in_debt() {
+        // Given
+        let limiter = IpRateLimiter::new(2, 2.0, MAX_TRACKED);
+        let ip = IpAddr::from_str("127.0.0.1").unwrap();
+
+        // When
+        let expensive = limiter.check_n(ip, 6);
+        let next = limiter.check(ip);
+
+        // Then
+        assert!(expensive.allowed);
+        assert!(!next.allowed);
+        assert_eq!(whole_seconds(next.retry_after), 5);
+    }
+
+    #[test]
+    fn test_paying_in_parts_is_admitted_like_paying_at_once() {
+        // Given
+        let limiter = IpRateLimiter::new(1, 1.0, MAX_TRACKED);
+        let ip = IpAddr::from_str("127.0.0.1").unwrap();
+
+        // When
+        let admitted = limiter.check_n(ip, 1);
+        let rest = limiter.check_more(ip, 5, 1);
+        let next = limiter.check_n(ip, 1);
+
+        // Then
+        assert!(admitted.allowed);
+        assert!(rest.allowed);
+        assert!(!next.allowed);
+        assert_eq!(whole_seconds(next.retry_after), 6);
     }
 }
@@ -0,0 +1,516 @@
+use actix_web::{delete, get, http::header, post, web, HttpRequest, HttpResponse};
+use log::debug;
+use lru::LruCache;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::errors::{ApiError, ProblemDetails};
+use crate::state::AppState;
+use crate::{limits, logging};
+use wordle_solver::constraints::Constraints;
+use wordle_solver::models::{Guess, PossibleWords, Turn, WordListPage, WordListQuery};
+use wordle_solver::word_set::WordSet;
+
+pub struct Session {
+    turns: Vec<Turn>,
+    guesses: Vec<Guess>,
+    // The words still possible after every turn so far, narrowed one turn at a
+    // time rather than refiltered from the whole dictionary.
+    candidates: WordSet,
+    last_used: Instant,
+}
+
+#[derive(Clone)]
+pub struct SessionSnapshot {
+    pub turns: Vec<Turn>,
+    pub guesses: Vec<Guess>,
+    pub candidates: WordSet,
+}
+
+impl Session {
+    fn snapshot(&self) -> SessionSnapshot {
+        SessionSnapshot {
+            turns: self.turns.clone(),
+            guesses: self.guesses.clone(),
+            candidates: self.candidates.clone(),
+        }
+    }
+}
+
+pub struct SessionStore {
+    // Least recently used first, so expired sessions can be dropped from the
+    // front and the store never holds more than `max_sessions`.
+    sessions: Mutex<LruCache<Uuid, Session>>,
+    ttl: Duration,
+}
+
+impl SessionStore {
+    pub fn new(ttl: Duration, max_sessions: NonZeroUsize) -> Self {
+        Self {
+            sessions: Mutex::new(LruCache::new(max_sessions)),
+            ttl,
+        }
+    }
+
+    /// Starts a session, forgetting the least recently used one if the store
+    /// is full.
+    pub fn create(&self, candidates: WordSet) -> Uuid {
+        let mut sessions = self.sessions.lock();
+        let now = Instant::now();
+        while sessions
+            .peek_lru()
+            .is_some_and(|(_, session)| now.duration_since(session.last_used) >= self.ttl)
+        {
+            sessions.pop_lru();
+        }
+        if sessions.len() == sessions.cap().get() {
+            debug!("Session store is full; forgot the least recently used session");
+        }
+
+        let id = Uuid::new_v4();
+        sessions.push(
+            id,
+            Session {
+                turns: Vec::new(),
+                guesses: Vec::new(),
+                candidates,
+                last_used: now,
+            },
+        );
+        id
+    }
+
+    /// Runs `f` against a live session and refreshes its expiry. Returns
+    /// `None` if the session never existed or has expired.
+    pub fn with_session<R>(&self, id: &Uuid, f: impl FnOnce(&mut Session) -> R) -> Option<R> {
+        let mut sessions = self.sessions.lock();
+        let now = Instant::now();
+
+        let session = sessions.get_mut(id)?;
+        if now.duration_since(session.last_used) >= self.ttl {
+            sessions.pop(id);
+            return None;
+        }
+        session.last_used = now;
+        Some(f(session))
+    }
+
+    pub fn remove(&self, id: &Uuid) -> bool {
+        self.sessions.lock().pop(id).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.lock().len()
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SessionResponse<'a> {
+    #[schema(value_type = String, example = "0f4b8f9e-3c1d-4c0a-9d5e-7a1b2c3d4e5f")]
+    pub id: Uuid,
+    pub turns: Vec<Turn>,
+    #[schema(value_type = PossibleWords)]
+    pub suggestions: WordListPage<'a>,
+}
+
+fn parse_session_id(id: &str, req: &HttpRequest) -> Result<Uuid, ApiError> {
+    Uuid::parse_str(id).map_err(|_| session_not_found(req))
+}
+
+fn session_not_found(req: &HttpRequest) -> ApiError {
+    ApiError::not_found(
+        "No session exists with this id, or it has expired.",
+        logging::instance(req),
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/sessions",
+    tag = "sessions",
+    params(WordListQuery),
+    responses(
+        (status = 201, description = "The new session and its opening suggestions", body = SessionResponse),
+        (status = 401, description = "Missing or unknown API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Disabled API key or denied address", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 503, description = "Still ranking the opening guesses, or too busy to rank more", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+#[post("")]
+pub async fn create_session(
+    state: web::Data<AppState>,
+    query: web::Query<WordListQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let candidates = WordSet::full(state.words.len());
+    let possible_words = state
+        .possible_words_for(&req, &Constraints::default(), &candidates, |cost| {
+            limits::charge(&req, cost)
+        })
+        .await?;
+    let id = state.sessions.create(candidates);
+
+    Ok(HttpResponse::Created()
+        .insert_header((header::LOCATION, format!("/sessions/{id}")))
+        .json(SessionResponse {
+            id,
+            turns: Vec::new(),
+            suggestions: possible_words.select(&query),
+        }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/sessions/{id}/guesses",
+    tag = "sessions",
+    params(("id" = String, Path, description = "Session id"), WordListQuery),
+    request_body = Turn,
+    responses(
+        (status = 200, description = "The session with updated suggestions", body = SessionResponse),
+        (status = 400, description = "Malformed guess", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or unknown API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Disabled API key or denied address", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown or expired session", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "Another guess was added to the session meanwhile", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 503, description = "Too busy to rank more", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+#[post("/{id}/guesses")]
+pub async fn add_guess(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    turn: web::Json<Turn>,
+    query: web::Query<WordListQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let id = parse_session_id(&id, &req)?;
+    let turn = turn.into_inner();
+
+    // The guess is only recorded once it has been paid for and scored, so a
+    // guess that was rate limited or turned away can simply be sent again.
+    let (turns_before, all_guesses, candidates) = state
+        .sessions
+        .with_session(&id, |session| {
+            let guesses = turn
+                .to_guesses(session.turns.len())
+                .map_err(|err| ApiError::bad_request(err, logging::instance(&req)))?
+                .0;
+            let mut candidates = session.candidates.clone();
+            candidates.intersect_with(
+                &state
+                    .word_index
+                    .filter(&Constraints::from_guesses(&guesses)),
+            );
+            let all_guesses: Vec<Guess> = session.guesses.iter().chain(&guesses).cloned().collect();
+            Ok((session.turns.len(), all_guesses, candidates))
+        })
+        .ok_or_else(|| session_not_found(&req))??;
+
+    let constraints = Constraints::from_guesses(&all_guesses);
+    let possible_words = state
+        .possible_words_for(&req, &constraints, &candidates, |cost| {
+            limits::charge(&req, cost)
+        })
+        .await?;
+
+    let turns = state
+        .sessions
+        .with_session(&id, |session| {
+            if session.turns.len() != turns_before {
+                return Err(ApiError::conflict(
+                    "Another guess was added to this session first.",
+                    logging::instance(&req),
+                ));
+            }
+            session.candidates = candidates;
+            session.guesses = all_guesses;
+            session.turns.push(turn);
+            Ok(session.turns.clone())
+        })
+        .ok_or_else(|| session_not_found(&req))??;
+
+    Ok(HttpResponse::Ok().json(SessionResponse {
+        id,
+        turns,
+        suggestions: possible_words.select(&query),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/sessions/{id}",
+    tag = "sessions",
+    params(("id" = String, Path, description = "Session id"), WordListQuery),
+    responses(
+        (status = 200, description = "The session and its current suggestions", body = SessionResponse),
+        (status = 401, description = "Missing or unknown API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Disabled API key or denied address", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown or expired session", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 503, description = "Still ranking the opening guesses, or too busy to rank more", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+#[get("/{id}")]
+pub async fn get_session(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    query: web::Query<WordListQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let id = parse_session_id(&id, &req)?;
+
+    let snapshot = state
+        .sessions
+        .with_session(&id, |session| session.snapshot())
+        .ok_or_else(|| session_not_found(&req))?;
+
+    let constraints = Constraints::from_guesses(&snapshot.guesses);
+    let possible_words = state
+        .possible_words_for(&req, &constraints, &snapshot.candidates, |cost| {
+            limits::charge(&req, cost)
+        })
+        .await?;
+
+    Ok(HttpResponse::Ok().json(SessionResponse {
+        id,
+        turns: snapshot.turns,
+        suggestions: possible_words.select(&query),
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/sessions/{id}",
+    tag = "sessions",
+    params(("id" = String, Path, description = "Session id")),
+    responses(
+        (status = 204, description = "The session was deleted"),
+        (status = 401, description = "Missing or unknown API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Disabled API key or denied address", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown or expired session", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+#[delete("/{id}")]
+pub async fn delete_session(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let id = parse_session_id(&id, &req)?;
+
+    if !state.sessions.remove(&id) {
+        return Err(session_not_found(&req));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::test_state;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::App;
+    use std::sync::mpsc;
+    use wordle_solver::models::Color;
+
+    fn state() -> web::Data<AppState> {
+        web::Data::new(test_state(&["crane", "slate", "aloof"], usize::MAX))
+    }
+
+    #[actix_web::test]
+    async fn test_guesses_turned_away_are_not_recorded() {
+        // Given
+        let state = state();
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .service(web::scope("/sessions").service(add_guess)),
+        )
+        .await;
+        let id = state.sessions.create(WordSet::full(state.words.len()));
+        let guess = || {
+            TestRequest::post()
+                .uri(&format!("/sessions/{id}/guesses"))
+                .set_json(Turn {
+                    word: "zzzzz".to_string(),
+                    colors: [Color::Grey; 5],
+                })
+                .to_request()
+        };
+        let (release, blocked) = mpsc::channel::<()>();
+        let busy = state.compute.run(move || blocked.recv().unwrap());
+
+        // When
+        let turned_away = test::call_service(&app, guess()).await;
+        let turns_after_refusal = state.sessions.with_session(&id, |s| s.turns.len());
+        release.send(()).unwrap();
+        busy.await.unwrap();
+        let accepted = test::call_service(&app, guess()).await;
+
+        // Then
+        assert_eq!(turned_away.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(turns_after_refusal, Some(0));
+        assert_eq!(accepted.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(accepted).await;
+        assert_eq!(body["turns"].as_array().unwrap().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_a_session_can_be_played_and_ended() {
+        // Given
+        let state = state();
+        state.warm_up();
+        let app = test::init_service(
+            App::new().app_data(state.clone()).service(
+                web::scope("/sessions")
+                    .service(create_session)
+                    .service(add_guess)
+                    .service(get_session)
+                    .service(delete_session),
+            ),
+        )
+        .await;
+
+        // When
+        let created =
+            test::call_service(&app, TestRequest::post().uri("/sessions").to_request()).await;
+        let location = created.headers().get(header::LOCATION).unwrap().clone();
+        let location = location.to_str().unwrap();
+        let guessed = test::call_service(
+            &app,
+            TestRequest::post()
+                .uri(&format!("{location}/guesses"))
+                .set_json(Turn {
+                    word: "crane".to_string(),
+                    colors: [
+                        Color::Grey,
+                        Color::Grey,
+                        Color::Green,
+                        Color::Grey,
+                        Color::Green,
+                    ],
+                })
+                .to_request(),
+        )
+        .await;
+        let fetched = test::call_service(&app, TestRequest::get().uri(location).to_request()).await;
+        let deleted =
+            test::call_service(&app, TestRequest::delete().uri(location).to_request()).await;
+        let gone = test::call_service(&app, TestRequest::get().uri(location).to_request()).await;
+
+        // Then
+        assert_eq!(created.status(), StatusCode::CREATED);
+        let created: serde_json::Value = test::read_body_json(created).await;
+        assert_eq!(created["suggestions"]["number_of_words"], 3);
+        assert_eq!(guessed.status(), StatusCode::OK);
+        let guessed: serde_json::Value = test::read_body_json(guessed).await;
+        assert_eq!(guessed["turns"][0]["word"], "crane");
+        assert_eq!(guessed["suggestions"]["word_list"][0]["word"], "slate");
+        assert_eq!(fetched.status(), StatusCode::OK);
+        let fetched: serde_json::Value = test::read_body_json(fetched).await;
+        assert_eq!(fetched["turns"], guessed["turns"]);
+        assert_eq!(deleted.status(), StatusCode::NO_CONTENT);
+        assert_eq!(gone.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_unknown_sessions_are_not_found() {
+        // Given
+        let app = test::init_service(
+            App::new().app_data(state()).service(
+                web::scope("/sessions")
+                    .service(add_guess)
+                    .service(get_session)
+                    .service(delete_session),
+            ),
+        )
+        .await;
+        let unknown = format!("/sessions/{}", Uuid::new_v4());
+        let requests = [
+            TestRequest::get().uri(&unknown),
+            TestRequest::get().uri("/sessions/not-a-uuid"),
+            TestRequest::delete().uri(&unknown),
+            TestRequest::post()
+                .uri(&format!("{unknown}/guesses"))
+                .set_json(Turn {
+                    word: "crane".to_string(),
+                    colors: [Color::Grey; 5],
+                }),
+        ];
+
+        for request in requests {
+            // When
+            let res = test::call_service(&app, request.to_request()).await;
+
+            // Then
+            assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[test]
+    fn test_full_store_forgets_the_least_recently_used_session() {
+        // Given
+        let store = SessionStore::new(Duration::from_secs(60), NonZeroUsize::new(2).unwrap());
+        let first = store.create(WordSet::full(10));
+        let second = store.create(WordSet::full(10));
+        store.with_session(&first, |_| ());
+
+        // When
+        let third = store.create(WordSet::full(10));
+
+        // Then
+        assert_eq!(store.len(), 2);
+        assert!(store.with_session(&first, |_| ()).is_some());
+        assert!(store.with_session(&second, |_| ()).is_none());
+        assert!(store.with_session(&third, |_| ()).is_some());
+    }
+
+    #[test]
+    fn test_sessions_expire_after_ttl() {
+        // Given
+        let store = SessionStore::new(Duration::ZERO, NonZeroUsize::new(8).unwrap());
+        let first = store.create(WordSet::full(10));
+
+        // When
+        let second = store.create(WordSet::full(10));
+        let session = store.with_session(&second, |session| session.snapshot());
+
+        // Then
+        assert!(
+            !store.sessions.lock().contains(&first),
+            "Creating a session should drop expired ones"
+        );
+        assert!(session.is_none(), "Session should have expired");
+        assert!(
+            !store.remove(&second),
+            "Expired session should have been dropped"
+        );
+    }
+
+    #[test]
+    fn test_sessions_are_independent() {
+        // Given
+        let store = SessionStore::new(Duration::from_secs(60), NonZeroUsize::new(8).unwrap());
+        let first = store.create(WordSet::full(10));
+        let second = store.create(WordSet::full(10));
+
+        // When
+        store.with_session(&first, |session| {
+            session.candidates.intersect_with(&WordSet::empty(10));
+        });
+
+        // Then
+        let first_count = store.with_session(&first, |s| s.candidates.iter().count());
+        let second_count = store.with_session(&second, |s| s.candidates.iter().count());
+        assert_eq!(first_count, Some(0));
+        assert_eq!(second_count, Some(10));
+    }
+}
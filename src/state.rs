@@ -1,23 +1,389 @@
-use crate::models::{PossibleWords, Word};
-use crate::rate_limit::IpRateLimiter;
+use crate::cache::ResponseCache;
+use crate::compute::{CancelOnDrop, ComputeError, ComputePool};
+use crate::errors::ApiError;
+use crate::logging;
+use crate::metrics::Metrics;
+use crate::sessions::SessionStore;
+use actix_web::HttpRequest;
+use futures_util::future::{BoxFuture, FutureExt, Shared, WeakShared};
+use log::debug;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use wordle_solver::constraints::Constraints;
+use wordle_solver::entropy::{
+    calculate_entropy_for_words, calculate_entropy_within, Budget, Ranking,
+};
+use wordle_solver::filters::{select_words, WordIndex};
+use wordle_solver::models::{PossibleWords, Word};
+use wordle_solver::word_set::WordSet;
+
+/// The detail of the `503` sent for the opening guess before `warm_up` is done.
+pub const STARTING_UP: &str = "The solver is still starting up. Try again shortly.";
+
+const BUSY: &str = "The solver is busy. Try again shortly.";
+
+// Scoring compares every candidate with every other, so charge one token per
+// million comparisons.
+const PAIRS_PER_TOKEN: usize = 1_000_000;
+
+/// How far ranking a request's candidates may go before it settles for
+/// estimated entropies.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScoringBudget {
+    pub max_comparisons: usize,
+    pub deadline: Duration,
+}
+
+// A ranking on the compute pool, which every request waiting for the same
+// constraints polls.
+type RankingJob = Shared<BoxFuture<'static, RankingOutcome>>;
+type RankingOutcome = Result<Option<Arc<PossibleWords>>, ComputeError>;
+
+// What answering some constraints takes, short of ranking the candidates.
+enum Lookup {
+    Ready(Arc<PossibleWords>),
+    // The opening guess, before `warm_up` is done.
+    StartingUp,
+    Unranked,
+}
 
 pub struct AppState {
     pub words: Vec<Word>,
-    pub empty_guess_cache: PossibleWords,
-    pub rate_limiter: IpRateLimiter,
+    pub word_index: WordIndex,
+    // Set once `warm_up` has ranked the whole dictionary.
+    empty_guess_cache: OnceLock<Arc<PossibleWords>>,
+    pub response_cache: ResponseCache,
+    pub sessions: SessionStore,
+    pub metrics: Arc<Metrics>,
+    pub compute: ComputePool,
+    budget: ScoringBudget,
+    // Weak, so that dropping every request waiting for a ranking cancels it.
+    in_flight: Mutex<HashMap<Constraints, WeakShared<BoxFuture<'static, RankingOutcome>>>>,
 }
 
 impl AppState {
     pub fn new(
         words: Vec<Word>,
-        empty_guess_cache: PossibleWords,
-        rate_limiter: IpRateLimiter,
+        response_cache: ResponseCache,
+        sessions: SessionStore,
+        metrics: Arc<Metrics>,
+        compute: ComputePool,
+        budget: ScoringBudget,
     ) -> Self {
         Self {
+            word_index: WordIndex::new(&words),
             words,
-            empty_guess_cache,
-            rate_limiter,
+            empty_guess_cache: OnceLock::new(),
+            response_cache,
+            sessions,
+            metrics,
+            compute,
+            budget,
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Ranks every word for the opening guess. This takes a while, so the
+    /// server starts without it and reports itself ready once it is done.
+    pub fn warm_up(&self) {
+        let ranked = self
+            .compute
+            .install(|| self.rank(&self.words.iter().collect::<Vec<_>>()));
+        let _ = self
+            .empty_guess_cache
+            .set(Arc::new(PossibleWords::new(ranked, self.words.len())));
+    }
+
+    pub fn is_warm(&self) -> bool {
+        self.empty_guess_cache.get().is_some()
+    }
+
+    // The tokens ranking `candidates` for `constraints` costs on top of the
+    // request itself. Scoring compares every candidate against every other,
+    // so the cost grows with the square of their number up to the budget, and
+    // answers from the cache are free. At the default budget that is up to 25
+    // tokens, more than the default bucket holds; the rate limiter admits
+    // such a request once the bucket is full and leaves it in debt.
+    fn compute_cost(&self, constraints: &Constraints, candidates: &WordSet) -> u32 {
+        if *constraints == Constraints::default()
+            || !constraints.is_satisfiable()
+            || self.response_cache.contains(constraints)
+        {
+            return 0;
         }
+        let candidates = candidates.len();
+        let pairs = candidates
+            .saturating_mul(candidates)
+            .min(self.budget.max_comparisons);
+        u32::try_from(pairs / PAIRS_PER_TOKEN).unwrap_or(u32::MAX)
     }
+
+    /// The ranked `candidates` satisfying `constraints`, which are ranked on
+    /// the compute pool unless they are cached. Requests for the same
+    /// constraints share one ranking, which stops if they are all dropped
+    /// before it is done; only the request that starts it is passed its cost
+    /// to `charge`. Logs how many candidates `req` had and how long ranking
+    /// them took.
+    pub async fn possible_words_for(
+        self: &Arc<Self>,
+        req: &HttpRequest,
+        constraints: &Constraints,
+        candidates: &WordSet,
+        charge: impl FnOnce(u32) -> Result<(), ApiError>,
+    ) -> Result<Arc<PossibleWords>, ApiError> {
+        let started = Instant::now();
+        let possible_words = match self.lookup(constraints) {
+            Lookup::Ready(possible_words) => possible_words,
+            Lookup::StartingUp => {
+                return Err(ApiError::unavailable(STARTING_UP, logging::instance(req)))
+            }
+            Lookup::Unranked => {
+                let job = self.ranking(constraints, candidates, charge)?;
+                let ranked = job.clone().await;
+                self.finished(constraints, &job);
+                ranked
+                    .map_err(|err| match err {
+                        ComputeError::Saturated => {
+                            ApiError::unavailable(BUSY, logging::instance(req))
+                        }
+                        ComputeError::Failed => ApiError::internal(
+                            "Ranking the candidates failed.",
+                            logging::instance(req),
+                        ),
+                    })?
+                    .ok_or_else(|| {
+                        ApiError::internal("Ranking was cancelled.", logging::instance(req))
+                    })?
+            }
+        };
+        logging::record_ranking(req, candidates.len(), started.elapsed());
+        Ok(possible_words)
+    }
+
+    // The ranking underway for `constraints`, or a new one once it has been
+    // paid for.
+    fn ranking(
+        self: &Arc<Self>,
+        constraints: &Constraints,
+        candidates: &WordSet,
+        charge: impl FnOnce(u32) -> Result<(), ApiError>,
+    ) -> Result<RankingJob, ApiError> {
+        let mut in_flight = self.in_flight.lock();
+        if let Some(job) = in_flight.get(constraints).and_then(WeakShared::upgrade) {
+            self.metrics.observe_coalesced();
+            return Ok(job);
+        }
+        charge(self.compute_cost(constraints, candidates))?;
+
+        let state = Arc::clone(self);
+        let (key, candidates) = (constraints.clone(), candidates.clone());
+        let cancel = CancelOnDrop::new();
+        let cancelled = cancel.flag();
+        let run = self
+            .compute
+            .run(move || state.rank_and_cache(key, &candidates, &cancelled));
+        let job = async move {
+            let _cancel = cancel;
+            run.await
+        }
+        .boxed()
+        .shared();
+
+        // Rankings every waiter gave up on leave their entries behind.
+        in_flight.retain(|_, job| job.upgrade().is_some());
+        if let Some(weak) = job.downgrade() {
+            in_flight.insert(constraints.clone(), weak);
+        }
+        Ok(job)
+    }
+
+    // Later requests for `constraints` find the answer in the cache, or rank
+    // afresh if it was only an estimate.
+    fn finished(&self, constraints: &Constraints, job: &RankingJob) {
+        let mut in_flight = self.in_flight.lock();
+        let current = in_flight.get(constraints).and_then(WeakShared::upgrade);
+        if current.is_none_or(|current| current.ptr_eq(job)) {
+            in_flight.remove(constraints);
+        }
+    }
+
+    fn lookup(&self, constraints: &Constraints) -> Lookup {
+        if *constraints == Constraints::default() {
+            return match self.empty_guess_cache.get() {
+                Some(opening) => Lookup::Ready(Arc::clone(opening)),
+                None => Lookup::StartingUp,
+            };
+        }
+        if !constraints.is_satisfiable() {
+            return Lookup::Ready(Arc::new(PossibleWords::new(Vec::new(), self.words.len())));
+        }
+        match self.response_cache.get(constraints) {
+            Some(cached) => Lookup::Ready(cached),
+            None => Lookup::Unranked,
+        }
+    }
+
+    // `None` if `cancelled` was set before ranking finished.
+    fn rank_and_cache(
+        &self,
+        constraints: Constraints,
+        candidates: &WordSet,
+        cancelled: &AtomicBool,
+    ) -> Option<Arc<PossibleWords>> {
+        debug!(
+            "Response cache miss ({} hits, {} misses)",
+            self.response_cache.hits(),
+            self.response_cache.misses()
+        );
+
+        let filtered_words = select_words(&self.words, candidates);
+        let ranking = if filtered_words.is_empty() {
+            Ranking {
+                words: Vec::new(),
+                partial: false,
+            }
+        } else {
+            let budget = Budget {
+                max_comparisons: self.budget.max_comparisons,
+                deadline: Instant::now().checked_add(self.budget.deadline),
+                cancelled,
+            };
+            self.rank_within(&filtered_words, &budget)?
+        };
+
+        let mut possible_words = PossibleWords::new(ranking.words, self.words.len());
+        possible_words.partial = ranking.partial;
+        let possible_words = Arc::new(possible_words);
+        // Estimates would outlive the load that called for them.
+        if !possible_words.partial {
+            self.response_cache
+                .insert(constraints, Arc::clone(&possible_words));
+        }
+        Some(possible_words)
+    }
+
+    fn rank(&self, words: &[&Word]) -> Vec<Word> {
+        let started = Instant::now();
+        let ranked = calculate_entropy_for_words(words);
+        self.metrics.observe_ranking(words.len(), started.elapsed());
+        ranked
+    }
+
+    fn rank_within(&self, words: &[&Word], budget: &Budget) -> Option<Ranking> {
+        let started = Instant::now();
+        let ranking = calculate_entropy_within(words, budget);
+        self.metrics.observe_ranking(words.len(), started.elapsed());
+        match &ranking {
+            Some(ranking) if ranking.partial => self.metrics.observe_cut_short("budget"),
+            Some(_) => {}
+            None => self.metrics.observe_cut_short("cancelled"),
+        }
+        ranking
+    }
+}
+
+/// `words`, all answers, ranked on a single compute thread with no queue, so
+/// that a second ranking at once is turned away.
+#[cfg(test)]
+pub(crate) fn test_state(words: &[&str], max_comparisons: usize) -> AppState {
+    use std::num::NonZeroUsize;
+
+    AppState::new(
+        words
+            .iter()
+            .map(|word| Word::new(word.to_string(), true))
+            .collect(),
+        ResponseCache::new(NonZeroUsize::new(8).unwrap()),
+        SessionStore::new(Duration::from_secs(60), NonZeroUsize::new(8).unwrap()),
+        Arc::new(Metrics::new()),
+        ComputePool::new(Some(1), 0).unwrap(),
+        ScoringBudget {
+            max_comparisons,
+            deadline: Duration::from_secs(60),
+        },
+    )
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use std::cell::RefCell;
+    use wordle_solver::models::{Color, Guess};
+
+    const WORDS: usize = 100;
+
+    // `WORDS` words without a z.
+    fn state(max_comparisons: usize) -> Arc<AppState> {
+        let letter = |n: usize| char::from(b'a' + (n % 25) as u8);
+        let words: Vec<String> = (0..WORDS)
+            .map(|i| [letter(i), letter(i / 25), 'r', 'o', 'e'].iter().collect())
+            .collect();
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        Arc::new(test_state(&words, max_comparisons))
+    }
+
+    fn without_z() -> Constraints {
+        Constraints::from_guesses(&[Guess {
+            turn: 0,
+            letter: 'z',
+            position: 0,
+            color: Color::Grey,
+        }])
+    }
+
+    #[actix_web::test]
+    async fn test_estimated_rankings_are_flagged_and_not_cached() {
+        // Given
+        let state = state(WORDS * 70);
+        let constraints = without_z();
+        let candidates = state.word_index.filter(&constraints);
+        let req = TestRequest::default().to_http_request();
+
+        // When
+        let possible_words = state
+            .possible_words_for(&req, &constraints, &candidates, |_| Ok(()))
+            .await
+            .unwrap();
+
+        // Then
+        assert!(possible_words.partial);
+        assert_eq!(possible_words.number_of_words, WORDS);
+        assert!(!state.response_cache.contains(&constraints));
+    }
+
+    #[actix_web::test]
+    async fn test_identical_requests_share_one_ranking() {
+        // Given
+        let state = state(usize::MAX);
+        let constraints = without_z();
+        let candidates = state.word_index.filter(&constraints);
+        let (first, second) = (
+            TestRequest::default().to_http_request(),
+            TestRequest::default().to_http_request(),
+        );
+        let cost = state.compute_cost(&constraints, &candidates);
+        let charges = RefCell::new(Vec::new());
+        let charge = |cost| {
+            charges.borrow_mut().push(cost);
+            Ok(())
+        };
+
+        // When
+        let (first, second) = futures_util::future::join(
+            state.possible_words_for(&first, &constraints, &candidates, charge),
+            state.possible_words_for(&second, &constraints, &candidates, charge),
+        )
+        .await;
+
+        // Then
+        assert!(Arc::ptr_eq(&first.unwrap(), &second.unwrap()));
+        assert_eq!(charges.into_inner(), [cost]);
+        assert_eq!(state.metrics.coalesced(), 1);
+        assert!(state.response_cache.contains(&constraints));
+        assert!(state.in_flight.lock().is_empty());
+    }
+}
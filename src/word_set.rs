@@ -0,0 +1,111 @@
+const BLOCK_BITS: usize = u64::BITS as usize;
+
+/// A set of indices into the dictionary, stored as a bitset.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct WordSet {
+    blocks: Vec<u64>,
+    capacity: usize,
+}
+
+impl WordSet {
+    pub fn empty(capacity: usize) -> Self {
+        WordSet {
+            blocks: vec![0; capacity.div_ceil(BLOCK_BITS)],
+            capacity,
+        }
+    }
+
+    pub fn full(capacity: usize) -> Self {
+        let mut set = WordSet {
+            blocks: vec![u64::MAX; capacity.div_ceil(BLOCK_BITS)],
+            capacity,
+        };
+        let trailing = capacity % BLOCK_BITS;
+        if let (Some(last), true) = (set.blocks.last_mut(), trailing != 0) {
+            *last = (1 << trailing) - 1;
+        }
+        set
+    }
+
+    pub fn insert(&mut self, index: usize) {
+        self.blocks[index / BLOCK_BITS] |= 1 << (index % BLOCK_BITS);
+    }
+
+    pub fn intersect_with(&mut self, other: &WordSet) {
+        for (block, other) in self.blocks.iter_mut().zip(&other.blocks) {
+            *block &= other;
+        }
+    }
+
+    pub fn difference_with(&mut self, other: &WordSet) {
+        for (block, other) in self.blocks.iter_mut().zip(&other.blocks) {
+            *block &= !other;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks
+            .iter()
+            .map(|block| block.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.iter().all(|&block| block == 0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.blocks.iter().enumerate().flat_map(|(i, &block)| {
+            let mut remaining = block;
+            std::iter::from_fn(move || {
+                if remaining == 0 {
+                    return None;
+                }
+                let bit = remaining.trailing_zeros() as usize;
+                remaining &= remaining - 1;
+                Some(i * BLOCK_BITS + bit)
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_set_only_contains_indices_below_capacity() {
+        // Given
+        let capacity = 70;
+
+        // When
+        let set = WordSet::full(capacity);
+
+        // Then
+        assert_eq!(set.iter().count(), 70);
+        assert_eq!(set.iter().last(), Some(69));
+    }
+
+    #[test]
+    fn test_set_operations() {
+        // Given
+        let mut left = WordSet::empty(130);
+        let mut right = WordSet::empty(130);
+        for i in [1, 64, 100, 129] {
+            left.insert(i);
+        }
+        for i in [64, 129] {
+            right.insert(i);
+        }
+
+        // When
+        let mut intersection = left.clone();
+        intersection.intersect_with(&right);
+        let mut difference = left.clone();
+        difference.difference_with(&right);
+
+        // Then
+        assert_eq!(intersection.iter().collect::<Vec<_>>(), vec![64, 129]);
+        assert_eq!(difference.iter().collect::<Vec<_>>(), vec![1, 100]);
+    }
+}
@@ -0,0 +1,77 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::{env, fs, process};
+
+fn write_list(name: &str, words: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("wordle-cli-{}-{name}", process::id()));
+    fs::write(&path, words).unwrap();
+    path
+}
+
+fn wordle_cli(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_wordle-cli"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_solves_from_the_given_word_lists() {
+    // Given
+    let answers = write_list("answers.txt", "crane\nslate\n");
+    let guesses = write_list("guesses.txt", "trace\n");
+    let args = [
+        "--answers",
+        answers.to_str().unwrap(),
+        "--guesses",
+        guesses.to_str().unwrap(),
+        "--strategy",
+        "answers",
+    ];
+
+    // When
+    let output = wordle_cli(&args, "slate ggggg\n");
+
+    // Then
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("Suggestion: "), "{stdout}");
+    assert!(stdout.ends_with("Solved in 1 guesses.\n"), "{stdout}");
+}
+
+#[test]
+fn test_fails_on_bad_arguments_or_missing_lists() {
+    // Given
+    let missing = env::temp_dir().join("wordle-cli-missing.txt");
+    let missing = missing.to_str().unwrap();
+    let malformed = write_list("malformed.txt", "crane\nsla\n");
+
+    // When
+    let unknown_flag = wordle_cli(&["--verbose"], "");
+    let bad_strategy = wordle_cli(&["--strategy", "random"], "");
+    let missing_list = wordle_cli(&["--answers", missing], "");
+    let malformed_list = wordle_cli(&["--answers", malformed.to_str().unwrap()], "");
+
+    // Then
+    for output in [&unknown_flag, &bad_strategy, &missing_list, &malformed_list] {
+        assert_eq!(output.status.code(), Some(1));
+    }
+    assert!(String::from_utf8_lossy(&unknown_flag.stderr).starts_with("Usage: wordle-cli"));
+    assert!(String::from_utf8_lossy(&bad_strategy.stderr).contains("Unknown strategy 'random'"));
+    for output in [&missing_list, &malformed_list] {
+        assert!(
+            String::from_utf8_lossy(&output.stderr).starts_with("Could not read the word lists:")
+        );
+    }
+}
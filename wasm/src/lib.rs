@@ -0,0 +1,118 @@
+//! The solver compiled to WebAssembly, with the NYT word lists built in, so a
+//! browser can suggest words without calling the API.
+
+use serde::Serialize;
+use std::cell::OnceCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wordle_solver::dictionary::words_from_lists;
+use wordle_solver::models::{GuessBody, PossibleWords, WordListQuery};
+use wordle_solver::{calculate_entropy_for_words, filter_words, Constraints, Word, WordIndex};
+
+const ANSWERS: &str = include_str!("../../wordle-nyt-answers.txt");
+const ALLOWED_GUESSES: &str = include_str!("../../wordle-nyt-allowed-guesses.txt");
+
+#[wasm_bindgen]
+pub struct Solver {
+    words: Vec<Word>,
+    index: WordIndex,
+    // Ranking the whole dictionary is by far the slowest case, so the opening
+    // suggestions are only worked out once.
+    opening: OnceCell<Rc<PossibleWords>>,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        let words =
+            words_from_lists(ANSWERS, ALLOWED_GUESSES).expect("The built-in word lists are valid");
+        Solver {
+            index: WordIndex::new(&words),
+            words,
+            opening: OnceCell::new(),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Solver {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Solver {
+        Solver::default()
+    }
+
+    /// Takes the same guesses as the body of `POST /possible-words` and,
+    /// optionally, an object with its query parameters. Returns the same
+    /// document the API would.
+    #[wasm_bindgen(js_name = possibleWords)]
+    pub fn possible_words(&self, guesses: JsValue, query: JsValue) -> Result<JsValue, JsError> {
+        let guesses: GuessBody = serde_wasm_bindgen::from_value(guesses)?;
+        let query: WordListQuery = if query.is_undefined() || query.is_null() {
+            WordListQuery::default()
+        } else {
+            serde_wasm_bindgen::from_value(query)?
+        };
+
+        let ranked = self.rank(&Constraints::from_guesses(&guesses.0));
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        Ok(ranked.select(&query).serialize(&serializer)?)
+    }
+}
+
+impl Solver {
+    fn rank(&self, constraints: &Constraints) -> Rc<PossibleWords> {
+        if *constraints == Constraints::default() {
+            let all_words: Vec<&Word> = self.words.iter().collect();
+            return Rc::clone(self.opening.get_or_init(|| self.compute(&all_words)));
+        }
+        let candidates = filter_words(&self.words, &self.index, constraints);
+        self.compute(&candidates)
+    }
+
+    fn compute(&self, candidates: &[&Word]) -> Rc<PossibleWords> {
+        Rc::new(PossibleWords::new(
+            calculate_entropy_for_words(candidates),
+            self.words.len(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wordle_solver::{Color, Guess};
+
+    #[test]
+    fn test_rank_uses_the_embedded_word_lists() {
+        // Given
+        let solver = Solver::new();
+        let guesses: Vec<Guess> = "crane"
+            .chars()
+            .zip([
+                Color::Grey,
+                Color::Grey,
+                Color::Green,
+                Color::Grey,
+                Color::Green,
+            ])
+            .enumerate()
+            .map(|(position, (letter, color))| Guess {
+                turn: 0,
+                letter,
+                position,
+                color,
+            })
+            .collect();
+
+        // When
+        let ranked = solver.rank(&Constraints::from_guesses(&guesses));
+
+        // Then
+        let page = serde_json::to_value(ranked.select(&WordListQuery::default())).unwrap();
+        assert_eq!(page["total_number_of_words"], solver.words.len());
+        assert!(page["word_list"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|word| word["word"] == "abate"));
+    }
+}